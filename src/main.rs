@@ -1,13 +1,21 @@
 use dioxus::prelude::*;
 
 mod services;
+mod syntax_highlighting;
 mod ui;
 
-use crate::services::automerge_service::start_automerge_service;
+use crate::services::automerge_service::{start_automerge_service, ACTIVE_FILE};
 use crate::services::connection_service::start_connection_service;
 use crate::services::node_service::{start_node_service, NodeCommand, NODE_INFO};
+use crate::services::presence_service::{start_presence_service, PresenceCommand};
 use crate::ui::automerge_document_view::AutomergeDocumentView;
+use crate::ui::diagnostics_view::DiagnosticsView;
+use crate::ui::event_log_view::EventLogView;
 use crate::ui::file_content_view::FileContentView;
+use crate::ui::search_view::SearchView;
+use crate::ui::session_recorder_view::SessionRecorderView;
+use crate::ui::snapshot_view::SnapshotView;
+use crate::ui::sync_status_badge::OverallSyncStatusBadge;
 use ui::connection_form::ConnectionForm;
 use ui::connection_view::ConnectionView;
 use ui::node_view::NodeInfoView;
@@ -39,6 +47,7 @@ enum Route {
 #[component]
 pub fn EthersyncWeb(join_code: String) -> Element {
     use_coroutine(start_automerge_service);
+    let presence_service = use_coroutine(start_presence_service);
     use_coroutine(start_connection_service);
     let node_service = use_coroutine(start_node_service);
 
@@ -52,8 +61,17 @@ pub fn EthersyncWeb(join_code: String) -> Element {
         });
     });
 
+    // Let peers see which file we are working on.
+    use_effect(move || {
+        ACTIVE_FILE.read();
+        presence_service.send(PresenceCommand::AnnounceToAll);
+    });
+
     rsx! {
-        h1 { "Ethersync-Web" }
+        h1 {
+            "Ethersync-Web "
+            OverallSyncStatusBadge { }
+        }
 
         NodeInfoView { }
 
@@ -63,6 +81,11 @@ pub fn EthersyncWeb(join_code: String) -> Element {
 
         ConnectionView { }
         AutomergeDocumentView { }
+        SearchView { }
         FileContentView { }
+        SnapshotView { }
+        EventLogView { }
+        SessionRecorderView { }
+        DiagnosticsView { }
     }
 }
@@ -1,3 +1,12 @@
 pub mod automerge_service;
 pub mod connection_service;
+pub mod diagnostics;
+pub mod document_schema;
+pub mod event_log;
 pub mod node_service;
+pub mod presence_service;
+pub mod remote_edits;
+pub mod search_index;
+pub mod session_recorder;
+pub mod snapshots;
+pub mod undo_history;
@@ -1,13 +1,48 @@
-use crate::services::connection_service::ConnectionCommand;
+use crate::services::connection_service::{
+    ConnectionCommand, PeerMessage, PeerRole, CONNECTED_PEERS,
+};
+use crate::services::diagnostics::{
+    measure, record_received_message, DocumentStatistics, DIAGNOSTICS,
+};
+use crate::services::document_schema::{
+    DocumentSchema, SchemaMismatch, DOCUMENT_STATES, FILES_KEY, SCHEMA_MISMATCHES,
+};
+use crate::services::event_log::{log_event, LogEvent, LogLevel, LogSource};
+use crate::services::node_service::NODE_INFO;
+use crate::services::presence_service::peer_name;
+use crate::services::remote_edits::{ChangedLines, RemoteEdit, REMOTE_EDIT};
+use crate::services::search_index::{SearchIndex, SearchQuery, TextSplice, SEARCH_INDEX};
+use crate::services::session_recorder::{
+    FrameDirection, ReplayedRecording, SessionRecording, REPLAYED_RECORDING, SESSION_RECORDING,
+};
+use crate::services::snapshots::{
+    load_snapshots, splices_between, store_snapshots, FileComparison, Snapshot, SnapshotComparison,
+    SNAPSHOTS, SNAPSHOT_COMPARISON,
+};
+use crate::services::undo_history::{
+    apply_splices, LocalEdit, RecordedSplice, UndoDirection, UndoHistory, UNDO_DEPTHS,
+};
 use anyhow::{anyhow, bail, Error, Result};
-use automerge::sync::{Message as AutomergeSyncMessage, State as SyncState, State, SyncDoc};
-use automerge::{AutoCommit, ChangeHash, ObjId, ReadDoc};
-use chrono::{DateTime, Local};
+use async_std::task::sleep;
+use automerge::sync::{
+    ChunkList, Message as AutomergeSyncMessage, State as SyncState, State, SyncDoc,
+};
+use automerge::transaction::{CommitOptions, Transactable};
+use automerge::{
+    ActorId, AutoCommit, ChangeHash, ObjId, ObjType, Patch, PatchAction, Prop, ReadDoc, ScalarValue,
+};
+use chrono::{Local, TimeDelta};
+use derive_more::Display;
 use dioxus::hooks::use_coroutine_handle;
-use dioxus::prelude::{Coroutine, GlobalSignal, Readable, Signal};
+use dioxus::prelude::{spawn, Coroutine, GlobalSignal, Readable, Signal};
 use futures::channel::mpsc::UnboundedReceiver;
 use futures::StreamExt;
-use std::fmt::Display;
+use iroh::NodeId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+/// How often the document is rebuilt from its saved form, see [`compact_document`].
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 #[derive(Clone, PartialEq)]
 pub struct AutomergeDocumentFile {
@@ -20,10 +55,12 @@ pub struct MessageDetails {
     last_sync: String,
     need: String,
     version: String,
+    /// Length of the encoded message in bytes, unknown for created messages until they are sent.
+    size: Option<usize>,
 }
 
 impl MessageDetails {
-    fn from_message(message: &AutomergeSyncMessage) -> Result<Self> {
+    fn from_message(message: &AutomergeSyncMessage, size: Option<usize>) -> Result<Self> {
         let last_sync: Vec<ChangeHash> = message
             .have
             .iter()
@@ -34,6 +71,7 @@ impl MessageDetails {
             heads: serde_json::to_string_pretty(&message.heads)?,
             need: serde_json::to_string_pretty(&message.need)?,
             version: format!("{:?}", message.version),
+            size,
         })
     }
 }
@@ -44,172 +82,1901 @@ impl Display for MessageDetails {
             f,
             "last_sync: {}\nheads: {}\nneed: {}\nversion: {}",
             self.last_sync, self.heads, self.need, self.version
-        )
+        )?;
+        if let Some(size) = self.size {
+            write!(f, "\nsize: {size} bytes")?;
+        }
+        Ok(())
     }
 }
 
 pub enum AutomergeEvent {
     AppliedSyncMessage {
-        date_time: DateTime<Local>,
         details: MessageDetails,
+        duration: TimeDelta,
+    },
+    ClosedDeletedFile {
+        file_name: String,
+    },
+    CompactedDocument {
+        size: usize,
+    },
+    CreatedSnapshot {
+        name: String,
     },
     CreatedSyncMessage {
-        date_time: DateTime<Local>,
         details: MessageDetails,
     },
     Error {
-        date_time: DateTime<Local>,
         error: Error,
     },
+    RefusedChanges {
+        remote_node_id: NodeId,
+        change_count: usize,
+    },
+    ReplacedText {
+        message: String,
+        replacement_count: usize,
+    },
+    ReplayedRecording {
+        frame_count: usize,
+    },
+    RestoredSnapshot {
+        message: String,
+    },
+    RevertedLocalEdit {
+        direction: UndoDirection,
+        file_name: String,
+    },
+    SchemaMismatch {
+        mismatch: SchemaMismatch,
+    },
+    UploadedFile {
+        file_name: String,
+        size: usize,
+    },
 }
 
 impl Display for AutomergeEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            AutomergeEvent::AppliedSyncMessage { date_time, details } => {
-                write!(f, "{date_time}: applied sync message:\n{details}")
+            AutomergeEvent::AppliedSyncMessage { details, duration } => {
+                write!(
+                    f,
+                    "applied sync message in {} ms:\n{details}",
+                    duration.num_milliseconds()
+                )
+            }
+            AutomergeEvent::ClosedDeletedFile { file_name } => {
+                write!(f, "closed tab of '{file_name}', which was deleted")
+            }
+            AutomergeEvent::CompactedDocument { size } => {
+                write!(f, "compacted document to {size} bytes")
             }
-            AutomergeEvent::CreatedSyncMessage { date_time, details } => {
-                write!(f, "{date_time}: created sync message:\n{details}")
+            AutomergeEvent::CreatedSnapshot { name } => write!(f, "created snapshot '{name}'"),
+            AutomergeEvent::CreatedSyncMessage { details } => {
+                write!(f, "created sync message:\n{details}")
+            }
+            AutomergeEvent::Error { error } => {
+                write!(f, "automerge error {error}")
+            }
+            AutomergeEvent::RefusedChanges {
+                remote_node_id,
+                change_count,
+            } => write!(
+                f,
+                "refused {change_count} change chunk(s) from viewer {}",
+                peer_name(*remote_node_id)
+            ),
+            AutomergeEvent::ReplacedText {
+                message,
+                replacement_count,
+            } => write!(f, "committed {replacement_count} replacement(s): {message}"),
+            AutomergeEvent::ReplayedRecording { frame_count } => {
+                write!(f, "replayed recording with {frame_count} frame(s)")
+            }
+            AutomergeEvent::RestoredSnapshot { message } => {
+                write!(f, "committed restore: {message}")
+            }
+            AutomergeEvent::RevertedLocalEdit {
+                direction,
+                file_name,
+            } => write!(f, "{direction} of a local edit in '{file_name}'"),
+            AutomergeEvent::SchemaMismatch { mismatch } => {
+                write!(f, "document doesn't match the ethersync format: {mismatch}")
+            }
+            AutomergeEvent::UploadedFile { file_name, size } => {
+                write!(f, "uploaded '{file_name}' ({size} bytes)")
+            }
+        }
+    }
+}
+
+impl LogEvent for AutomergeEvent {
+    fn level(&self) -> LogLevel {
+        match self {
+            AutomergeEvent::AppliedSyncMessage { .. }
+            | AutomergeEvent::CompactedDocument { .. }
+            | AutomergeEvent::CreatedSyncMessage { .. } => LogLevel::Debug,
+            AutomergeEvent::Error { .. } => LogLevel::Error,
+            AutomergeEvent::ClosedDeletedFile { .. }
+            | AutomergeEvent::RefusedChanges { .. }
+            | AutomergeEvent::SchemaMismatch { .. } => LogLevel::Warning,
+            AutomergeEvent::CreatedSnapshot { .. }
+            | AutomergeEvent::ReplacedText { .. }
+            | AutomergeEvent::ReplayedRecording { .. }
+            | AutomergeEvent::RestoredSnapshot { .. }
+            | AutomergeEvent::RevertedLocalEdit { .. }
+            | AutomergeEvent::UploadedFile { .. } => LogLevel::Info,
+        }
+    }
+
+    fn source(&self) -> LogSource {
+        LogSource::Automerge
+    }
+
+    fn peer(&self) -> Option<NodeId> {
+        match self {
+            AutomergeEvent::RefusedChanges { remote_node_id, .. } => Some(*remote_node_id),
+            _ => None,
+        }
+    }
+}
+
+/// How far the document of a peer is from ours, as far as we know from its sync messages.
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum SyncStatus {
+    #[display("in sync")]
+    InSync,
+    /// Only we know how many changes the peer is missing, for its side we only know the heads.
+    #[display("syncing ({changes_to_send} change(s) to send, {missing_heads} head(s) to receive)")]
+    Syncing {
+        changes_to_send: usize,
+        missing_heads: usize,
+    },
+    /// Both sides have changes the other one is missing.
+    #[display(
+        "diverged ({changes_to_send} change(s) to send, {missing_heads} head(s) to receive)"
+    )]
+    Diverged {
+        changes_to_send: usize,
+        missing_heads: usize,
+    },
+}
+
+impl SyncStatus {
+    fn compute(doc: &mut AutoCommit, state: &State) -> Self {
+        let Some(their_heads) = state.their_heads.as_ref() else {
+            return SyncStatus::Syncing {
+                changes_to_send: 0,
+                missing_heads: 0,
+            };
+        };
+
+        let mut our_heads = doc.get_heads();
+        let mut their_heads = their_heads.clone();
+        our_heads.sort();
+        their_heads.sort();
+        if our_heads == their_heads {
+            return SyncStatus::InSync;
+        }
+
+        // Changes we don't have can't be counted, only the heads of them which we know about.
+        let missing_heads = doc.get_missing_deps(&their_heads).len();
+        // Without all of their heads, only the shared heads tell us what they already have.
+        let known_heads = if missing_heads == 0 {
+            &their_heads
+        } else {
+            &state.shared_heads
+        };
+        let changes_to_send = doc.get_changes(known_heads).len();
+
+        if missing_heads > 0 && changes_to_send > 0 {
+            SyncStatus::Diverged {
+                changes_to_send,
+                missing_heads,
             }
-            AutomergeEvent::Error { date_time, error } => {
-                write!(f, "{date_time}: automerge error {error}")
+        } else {
+            SyncStatus::Syncing {
+                changes_to_send,
+                missing_heads,
             }
         }
     }
 }
 
-pub static AUTOMERGE_EVENTS: GlobalSignal<Vec<AutomergeEvent>> = Signal::global(Vec::new);
+pub static SYNC_STATUS: GlobalSignal<HashMap<NodeId, SyncStatus>> = Signal::global(HashMap::new);
+
+fn update_sync_status(doc: &mut AutoCommit, states: &HashMap<NodeId, State>, node_id: NodeId) {
+    if let Some(state) = states.get(&node_id) {
+        let sync_status = SyncStatus::compute(doc, state);
+        SYNC_STATUS.write().insert(node_id, sync_status);
+    }
+}
 
 pub static FILES: GlobalSignal<Vec<String>> = Signal::global(Vec::new);
-pub static SELECTED_FILE: GlobalSignal<Option<AutomergeDocumentFile>> = Signal::global(|| None);
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinaryFile {
+    pub file_name: String,
+    pub size: usize,
+}
+
+/// Files stored as bytes instead of text, ordered by name.
+pub static BINARY_FILES: GlobalSignal<Vec<BinaryFile>> = Signal::global(Vec::new);
+
+#[derive(Clone, PartialEq)]
+pub struct BinaryFileContent {
+    pub file_name: String,
+    pub content: Vec<u8>,
+}
+
+/// Binary file selected for preview and download, as it can't be opened in a tab.
+pub static SELECTED_BINARY_FILE: GlobalSignal<Option<BinaryFileContent>> = Signal::global(|| None);
+
+/// Who last wrote each line of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileBlame {
+    pub file_name: String,
+    /// Node which inserted the newest character of a line, unless the line is empty or the actor
+    /// is no node ID.
+    pub line_authors: Vec<Option<NodeId>>,
+}
+
+/// Blame shown for one of the files, kept up to date with the changes to it.
+pub static FILE_BLAME: GlobalSignal<Option<FileBlame>> = Signal::global(|| None);
+
+/// Files opened in tabs, in the order of the tabs.
+pub static OPEN_FILES: GlobalSignal<Vec<AutomergeDocumentFile>> = Signal::global(Vec::new);
+/// Name of the file in the active tab.
+pub static ACTIVE_FILE: GlobalSignal<Option<String>> = Signal::global(|| None);
+/// Notice for the user about tabs closed by changes of peers.
+pub static FILE_NOTICE: GlobalSignal<Option<String>> = Signal::global(|| None);
+
+/// Returns the file in the active tab.
+pub fn active_file() -> Option<AutomergeDocumentFile> {
+    let active_file_name = ACTIVE_FILE.read().clone()?;
+    OPEN_FILES
+        .read()
+        .iter()
+        .find(|open_file| open_file.file_name == active_file_name)
+        .cloned()
+}
+
+// TODO: load content from local storage?
+// see https://github.com/ethersync/ethersync/blob/v0.7.0/daemon/src/document.rs#L37
+const INITIAL_DOC: &[u8] = &[
+    133, 111, 74, 131, 61, 157, 231, 85, 0, 118, 1, 16, 120, 107, 104, 47, 215, 9, 76, 32, 132,
+    136, 60, 124, 152, 120, 144, 182, 1, 143, 164, 31, 13, 102, 61, 139, 125, 246, 189, 135, 97,
+    16, 167, 63, 30, 215, 249, 60, 227, 113, 111, 61, 55, 138, 234, 94, 30, 142, 166, 78, 250, 6,
+    1, 2, 3, 2, 19, 2, 35, 2, 64, 2, 86, 2, 7, 21, 14, 33, 2, 35, 2, 52, 1, 66, 2, 86, 2, 128, 1,
+    2, 127, 0, 127, 1, 127, 2, 127, 0, 127, 0, 127, 7, 126, 5, 102, 105, 108, 101, 115, 6, 115,
+    116, 97, 116, 101, 115, 2, 0, 2, 1, 2, 2, 0, 2, 0, 2, 0, 0,
+];
+
+fn load_initial_doc() -> Result<AutoCommit> {
+    AutoCommit::load(INITIAL_DOC).map_err(|error| anyhow!(error))
+}
+
+/// Returns how long receiving the message took, without forking and merging the document.
 async fn apply_message(
     doc: &mut AutoCommit,
     state: &mut State,
     message: AutomergeSyncMessage,
-) -> Result<Vec<ChangeHash>> {
+) -> Result<TimeDelta> {
     let mut new_doc = doc.fork();
-    new_doc.sync().receive_sync_message(state, message)?;
-    Ok(doc.merge(&mut new_doc)?)
+    let (received, duration) = measure(|| new_doc.sync().receive_sync_message(state, message));
+    received?;
+    doc.merge(&mut new_doc)?;
+    Ok(duration)
 }
 
-fn object_id_by_name(doc: &AutoCommit, parent: ObjId, name: &str) -> Result<ObjId> {
-    if let Some(object_id) = doc.get(parent, name)?.map(|entry| entry.1) {
-        return Ok(object_id);
-    }
-
-    bail!("no object '{name}' found!")
+fn files_object(doc: &AutoCommit) -> Result<ObjId> {
+    Ok(DocumentSchema::read(doc, None)?.files)
 }
 
-fn files_object(doc: &AutoCommit) -> Result<ObjId> {
-    object_id_by_name(doc, automerge::ROOT, "files")
+fn file_object(doc: &AutoCommit, file_name: &str) -> Result<ObjId> {
+    DocumentSchema::read(doc, None)?.file(doc, file_name, None)
 }
 
 fn files(doc: &AutoCommit) -> Result<Vec<String>> {
-    Ok(doc.keys(files_object(doc)?).collect())
+    Ok(DocumentSchema::read(doc, None)?.file_names(doc, None))
 }
 
 fn file_content(doc: &AutoCommit, file_name: &str) -> Result<String> {
-    let object_id = object_id_by_name(doc, files_object(doc)?, file_name)?;
-    Ok(doc.text(object_id)?)
+    Ok(doc.text(file_object(doc, file_name)?)?)
 }
 
-fn select_file(doc: &AutoCommit, file_name: &str) -> Result<()> {
-    *SELECTED_FILE.write() = Some(AutomergeDocumentFile {
+/// Contents of all files as of the given heads.
+fn file_contents_at(doc: &AutoCommit, heads: &[ChangeHash]) -> Result<BTreeMap<String, String>> {
+    let schema = DocumentSchema::read(doc, Some(heads))?;
+
+    let mut file_contents = BTreeMap::new();
+    for file_name in schema.file_names(doc, Some(heads)) {
+        let object_id = schema.file(doc, &file_name, Some(heads))?;
+        let content = doc.text_at(&object_id, heads)?;
+        file_contents.insert(file_name, content);
+    }
+    Ok(file_contents)
+}
+
+/// Checks the document against the ethersync format and reads its states, logging mismatches
+/// when they first appear.
+///
+/// Returns the schema, unless the document has no usable files.
+fn check_schema(doc: &AutoCommit) -> Result<Option<DocumentSchema>> {
+    let mismatches = DocumentSchema::mismatches(doc)?;
+    let mut reported_mismatches = SCHEMA_MISMATCHES.write();
+    for mismatch in &mismatches {
+        if !reported_mismatches.contains(mismatch) {
+            log_event(AutomergeEvent::SchemaMismatch {
+                mismatch: mismatch.clone(),
+            });
+        }
+    }
+    *reported_mismatches = mismatches;
+    drop(reported_mismatches);
+
+    let schema = match DocumentSchema::read(doc, None) {
+        Ok(schema) => schema,
+        Err(error) if error.is::<SchemaMismatch>() => {
+            DOCUMENT_STATES.write().clear();
+            return Ok(None);
+        }
+        Err(error) => return Err(error),
+    };
+    *DOCUMENT_STATES.write() = schema.states(doc)?;
+    Ok(Some(schema))
+}
+
+/// Re-reads an open file, or opens it in a new tab after the existing ones.
+fn load_file(doc: &AutoCommit, file_name: &str) -> Result<()> {
+    let file = AutomergeDocumentFile {
         file_name: file_name.to_owned(),
         content: file_content(doc, file_name)?,
+    };
+
+    let mut open_files = OPEN_FILES.write();
+    match open_files
+        .iter_mut()
+        .find(|open_file| open_file.file_name == file_name)
+    {
+        Some(open_file) => *open_file = file,
+        None => open_files.push(file),
+    }
+    Ok(())
+}
+
+fn select_file(doc: &AutoCommit, file_name: &str) -> Result<()> {
+    load_file(doc, file_name)?;
+    *ACTIVE_FILE.write() = Some(file_name.to_owned());
+    Ok(())
+}
+
+fn close_file(file_name: &str) {
+    let mut open_files = OPEN_FILES.write();
+    let Some(position) = open_files
+        .iter()
+        .position(|open_file| open_file.file_name == file_name)
+    else {
+        return;
+    };
+    open_files.remove(position);
+
+    let mut active_file = ACTIVE_FILE.write();
+    if active_file.as_deref() == Some(file_name) {
+        // Activate the neighbouring tab, like editors do.
+        *active_file = open_files
+            .get(position)
+            .or_else(|| open_files.last())
+            .map(|open_file| open_file.file_name.clone());
+    }
+}
+
+fn close_deleted_file(file_name: &str) {
+    close_file(file_name);
+    *FILE_NOTICE.write() = Some(format!(
+        "'{file_name}' was deleted by a peer and has been closed."
+    ));
+    log_event(AutomergeEvent::ClosedDeletedFile {
+        file_name: file_name.to_owned(),
     });
+}
+
+fn open_file_names() -> Vec<String> {
+    OPEN_FILES
+        .read()
+        .iter()
+        .map(|open_file| open_file.file_name.clone())
+        .collect()
+}
+
+/// Re-reads a file into the [`SEARCH_INDEX`], unless the index isn't built yet.
+fn index_file(doc: &AutoCommit, file_name: &str) -> Result<()> {
+    if !SEARCH_INDEX.read().is_complete() {
+        return Ok(());
+    }
+
+    let content = file_content(doc, file_name)?;
+    SEARCH_INDEX.write().insert_file(file_name, content);
+    Ok(())
+}
+
+/// Re-reads a binary file into [`BINARY_FILES`] and the [`SELECTED_BINARY_FILE`].
+fn load_binary_file(doc: &AutoCommit, schema: &DocumentSchema, file_name: &str) -> Result<()> {
+    let binary_file = BinaryFile {
+        file_name: file_name.to_owned(),
+        size: schema.binary_file_size(doc, file_name)?,
+    };
+
+    let mut binary_files = BINARY_FILES.write();
+    match binary_files.binary_search_by(|binary_file| binary_file.file_name.as_str().cmp(file_name))
+    {
+        Ok(position) => binary_files[position] = binary_file,
+        Err(position) => binary_files.insert(position, binary_file),
+    }
+
+    drop(binary_files);
+
+    // Only the content of the selected file is kept.
+    if SELECTED_BINARY_FILE
+        .read()
+        .as_ref()
+        .is_some_and(|selected| selected.file_name == file_name)
+    {
+        let content = schema.binary_file(doc, file_name)?;
+        if let Some(selected) = SELECTED_BINARY_FILE.write().as_mut() {
+            selected.content = content;
+        }
+    }
+    Ok(())
+}
+
+fn select_binary_file(doc: &AutoCommit, file_name: &str) -> Result<()> {
+    let schema = DocumentSchema::read(doc, None)?;
+    *SELECTED_BINARY_FILE.write() = Some(BinaryFileContent {
+        file_name: file_name.to_owned(),
+        content: schema.binary_file(doc, file_name)?,
+    });
+    Ok(())
+}
+
+/// Re-reads the file list, the search index and all open files from the whole document.
+fn refresh_files(doc: &AutoCommit) -> Result<()> {
+    let schema = DocumentSchema::read(doc, None)?;
+    let file_names = schema.file_names(doc, None);
+    if *FILES.read() != file_names {
+        *FILES.write() = file_names;
+    }
+
+    BINARY_FILES.write().clear();
+    let binary_file_names = schema.binary_file_names(doc);
+    for file_name in &binary_file_names {
+        load_binary_file(doc, &schema, file_name)?;
+    }
+    let mut selected_binary_file = SELECTED_BINARY_FILE.write();
+    if selected_binary_file
+        .as_ref()
+        .is_some_and(|selected| !binary_file_names.contains(&selected.file_name))
+    {
+        *selected_binary_file = None;
+    }
+    drop(selected_binary_file);
+
+    let search_index_was_complete = SEARCH_INDEX.read().is_complete();
+    SEARCH_INDEX.write().clear();
+    if search_index_was_complete {
+        build_search_index(doc)?;
+    }
+
+    for file_name in open_file_names() {
+        if FILES.read().contains(&file_name) {
+            load_file(doc, &file_name)?;
+        } else {
+            close_deleted_file(&file_name);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the content of all files into the [`SEARCH_INDEX`], which is put off until the first
+/// search.
+fn build_search_index(doc: &AutoCommit) -> Result<()> {
+    if SEARCH_INDEX.read().is_complete() {
+        return Ok(());
+    }
+
+    let mut search_index = SearchIndex::default();
+    for file_name in FILES.read().iter() {
+        search_index.insert_file(file_name, file_content(doc, file_name)?);
+    }
+    search_index.mark_complete();
+    *SEARCH_INDEX.write() = search_index;
+    Ok(())
+}
+
+/// Replaces `delete` characters at the character `index` with `insert`, like Automerge does.
+fn splice_text(text: &mut String, index: usize, delete: usize, insert: &str) {
+    let byte_index = |char_index: usize| {
+        text.char_indices()
+            .nth(char_index)
+            .map_or(text.len(), |(byte_index, _)| byte_index)
+    };
+    let start = byte_index(index);
+    let end = byte_index(index + delete);
+    text.replace_range(start..end, insert);
+}
+
+fn splice_file(file_name: &str, index: usize, delete: usize, insert: &str) {
+    if let Some(content) = SEARCH_INDEX.write().file_content_mut(file_name) {
+        splice_text(content, index, delete, insert);
+    }
+
+    if let Some(open_file) = OPEN_FILES
+        .write()
+        .iter_mut()
+        .find(|open_file| open_file.file_name == file_name)
+    {
+        splice_text(&mut open_file.content, index, delete, insert);
+    }
+}
+
+/// Drops a text file, which is gone from the document, from [`FILES`], [`OPEN_FILES`] and the
+/// [`SEARCH_INDEX`].
+fn remove_text_file(file_name: &str) {
+    FILES.write().retain(|name| name != file_name);
+    SEARCH_INDEX.write().remove_file(file_name);
+    if open_file_names().iter().any(|name| name == file_name) {
+        close_deleted_file(file_name);
+    }
+}
+
+fn remove_binary_file(file_name: &str) {
+    BINARY_FILES
+        .write()
+        .retain(|binary_file| binary_file.file_name != file_name);
+
+    let mut selected_binary_file = SELECTED_BINARY_FILE.write();
+    if selected_binary_file
+        .as_ref()
+        .is_some_and(|selected| selected.file_name == file_name)
+    {
+        *selected_binary_file = None;
+    }
+}
+
+fn remove_file(file_name: &str) {
+    remove_text_file(file_name);
+    remove_binary_file(file_name);
+}
+
+/// Updates [`FILES`], [`BINARY_FILES`], [`OPEN_FILES`] and the [`SEARCH_INDEX`] from the patches
+/// of an applied sync message, so only the affected entries are touched instead of re-reading the
+/// whole document.
+///
+/// Returns the lines of the [`ACTIVE_FILE`] which the patches spliced.
+fn apply_patches(
+    doc: &AutoCommit,
+    schema: &DocumentSchema,
+    patches: Vec<Patch>,
+) -> Result<ChangedLines> {
+    let files_object = &schema.files;
+    let open_file_names = open_file_names();
+    let active_file_name = ACTIVE_FILE.read().clone();
+    let mut changed_lines = ChangedLines::default();
+    // Once re-read, a file already contains the text of all later patches.
+    let mut reloaded_file_names = Vec::new();
+
+    for patch in patches {
+        if patch.obj == automerge::ROOT {
+            if let PatchAction::PutMap { key, .. } | PatchAction::DeleteMap { key } = &patch.action
+            {
+                if key == FILES_KEY {
+                    // The whole files object was replaced.
+                    refresh_files(doc)?;
+                    return Ok(ChangedLines::default());
+                }
+            }
+            continue;
+        }
+
+        if patch.obj == *files_object {
+            match patch.action {
+                // Entries which are no texts are reported by the schema check instead.
+                PatchAction::PutMap { key, .. } if schema.file(doc, &key, None).is_ok() => {
+                    remove_binary_file(&key);
+                    let mut files = FILES.write();
+                    if let Err(position) = files.binary_search(&key) {
+                        files.insert(position, key.clone());
+                    }
+                    drop(files);
+
+                    // The file may have been replaced by a new text object.
+                    index_file(doc, &key)?;
+                    if open_file_names.contains(&key) {
+                        load_file(doc, &key)?;
+                    }
+                    reloaded_file_names.push(key);
+                }
+                PatchAction::PutMap { key, .. } if schema.binary_file_size(doc, &key).is_ok() => {
+                    remove_text_file(&key);
+                    load_binary_file(doc, schema, &key)?;
+                }
+                PatchAction::PutMap { key, .. } | PatchAction::DeleteMap { key } => {
+                    remove_file(&key);
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let Some(file_name) = patch.path.iter().find_map(|(object_id, prop)| match prop {
+            Prop::Map(file_name) if object_id == files_object => Some(file_name),
+            _ => None,
+        }) else {
+            continue;
+        };
+        if reloaded_file_names.contains(file_name) {
+            continue;
+        }
+
+        let (index, delete, insert) = match patch.action {
+            PatchAction::SpliceText { index, value, .. } => (index, 0, value.make_string()),
+            PatchAction::DeleteSeq { index, length } => (index, length, String::new()),
+            _ => continue,
+        };
+        if active_file_name.as_ref() == Some(file_name) {
+            // Borrowed, cloning the content for every splice adds up for large files.
+            if let Some(active_file) = OPEN_FILES
+                .read()
+                .iter()
+                .find(|open_file| open_file.file_name == *file_name)
+            {
+                changed_lines.record_splice(&active_file.content, index, delete, &insert);
+            }
+        }
+        splice_file(file_name, index, delete, &insert);
+    }
+    Ok(changed_lines)
+}
+
+/// Makes our session actor the actor of upcoming local changes, so peers can tell who made them.
+///
+/// The actor is our node ID followed by a random suffix per session, from which
+/// [`actor_node_id`] recovers the node ID.
+fn set_node_actor(doc: &mut AutoCommit) {
+    let Some(actor) = NODE_INFO
+        .read()
+        .as_ref()
+        .map(|node_info| node_info.actor_id.clone())
+    else {
+        return;
+    };
+    if *doc.get_actor() != actor {
+        doc.set_actor(actor);
+    }
+}
+
+/// Commits the pending operations of a local change.
+fn commit_local_change(doc: &mut AutoCommit, message: String) -> Option<ChangeHash> {
+    doc.commit_with(
+        CommitOptions::default()
+            .with_message(message)
+            .with_time(Local::now().timestamp()),
+    )
+}
+
+fn update_undo_depth(undo_histories: &HashMap<String, UndoHistory>, file_name: &str) {
+    let depth = undo_histories
+        .get(file_name)
+        .map(UndoHistory::depth)
+        .unwrap_or_default();
+    UNDO_DEPTHS.write().insert(file_name.to_owned(), depth);
+}
+
+/// Recorded splices of a local change, per file name.
+type ChangedFiles = Vec<(String, Vec<RecordedSplice>)>;
+
+fn record_local_edits(
+    undo_histories: &mut HashMap<String, UndoHistory>,
+    change_hash: Option<ChangeHash>,
+    changed_files: ChangedFiles,
+) {
+    for (file_name, recorded_splices) in changed_files {
+        undo_histories
+            .entry(file_name.clone())
+            .or_default()
+            .record(LocalEdit::new(change_hash, recorded_splices));
+        update_undo_depth(undo_histories, &file_name);
+    }
+}
+
+/// Whether a patch changes what [`check_schema`] looks at, unlike edits within files.
+fn changes_schema(schema: &DocumentSchema, patch: &Patch) -> bool {
+    patch.obj == automerge::ROOT
+        || patch.obj == schema.files
+        || schema.states.as_ref().is_some_and(|states| {
+            patch.obj == *states || patch.path.iter().any(|(object_id, _)| object_id == states)
+        })
+}
+
+/// Checks the schema and applies the changes made since `old_heads` to the files.
+///
+/// Returns the changed lines of the [`ACTIVE_FILE`], see [`apply_patches`].
+fn update_files_since(doc: &mut AutoCommit, old_heads: &[ChangeHash]) -> Result<ChangedLines> {
+    let new_heads = doc.get_heads();
+    let patches = doc.diff(old_heads, &new_heads);
+
+    // Most changes are edits within files, checking every file entry for each of them would be
+    // slow in documents with thousands of files.
+    let schema = match DocumentSchema::read(doc, None) {
+        Ok(schema) if !patches.iter().any(|patch| changes_schema(&schema, patch)) => Some(schema),
+        _ => check_schema(doc)?,
+    };
+    let changed_lines = match schema {
+        Some(schema) => apply_patches(doc, &schema, patches)?,
+        None => {
+            for file_name in FILES.read().clone() {
+                remove_text_file(&file_name);
+            }
+            for binary_file in BINARY_FILES.read().clone() {
+                remove_binary_file(&binary_file.file_name);
+            }
+            ChangedLines::default()
+        }
+    };
+    refresh_blame(doc)?;
+    Ok(changed_lines)
+}
+
+/// Node ID in an actor ID, as nodes use it for their changes, see [`set_node_actor`].
+fn actor_node_id(actor: &ActorId) -> Option<NodeId> {
+    let bytes = <[u8; 32]>::try_from(actor.to_bytes().get(..32)?).ok()?;
+    NodeId::from_bytes(&bytes).ok()
+}
+
+fn blame_file(doc: &AutoCommit, file_name: &str) -> Result<FileBlame> {
+    let object_id = file_object(doc, file_name)?;
+    let content = doc.text(&object_id)?;
+
+    let mut line_authors = Vec::new();
+    // The operation with the highest counter inserted the newest character of the line.
+    let mut newest_insertion: Option<(u64, ActorId)> = None;
+    for (character, item) in content.chars().zip(doc.list_range(&object_id, ..)) {
+        if character == '\n' {
+            line_authors.push(
+                newest_insertion
+                    .take()
+                    .and_then(|(_, actor)| actor_node_id(&actor)),
+            );
+            continue;
+        }
+
+        if let ObjId::Id(counter, actor, _) = item.id() {
+            if newest_insertion
+                .as_ref()
+                .is_none_or(|(newest_counter, _)| counter > *newest_counter)
+            {
+                newest_insertion = Some((counter, actor));
+            }
+        }
+    }
+    line_authors.push(newest_insertion.and_then(|(_, actor)| actor_node_id(&actor)));
+
+    Ok(FileBlame {
+        file_name: file_name.to_owned(),
+        line_authors,
+    })
+}
+
+/// Recomputes the shown blame, or hides it if its file is gone.
+fn refresh_blame(doc: &AutoCommit) -> Result<()> {
+    let Some(file_name) = FILE_BLAME
+        .read()
+        .as_ref()
+        .map(|file_blame| file_blame.file_name.clone())
+    else {
+        return Ok(());
+    };
+
+    let file_blame = if FILES.read().contains(&file_name) {
+        Some(blame_file(doc, &file_name)?)
+    } else {
+        None
+    };
+    if *FILE_BLAME.read() != file_blame {
+        *FILE_BLAME.write() = file_blame;
+    }
+    Ok(())
+}
+
+/// Names of the peers who made the changes since `old_heads`, or of the peer who sent them if
+/// the actors are unknown.
+fn change_authors(doc: &mut AutoCommit, old_heads: &[ChangeHash], sender: NodeId) -> Vec<String> {
+    let local_node_id = NODE_INFO.read().as_ref().map(|node_info| node_info.node_id);
+    let mut authors = Vec::new();
+    for change in doc.get_changes(old_heads) {
+        let Some(node_id) = actor_node_id(change.actor_id()) else {
+            continue;
+        };
+        let name = peer_name(node_id);
+        if Some(node_id) != local_node_id && !authors.contains(&name) {
+            authors.push(name);
+        }
+    }
+
+    if authors.is_empty() {
+        authors.push(peer_name(sender));
+    }
+    authors
+}
+
+/// Shows which lines of the active file peers changed, so the text doesn't just jump.
+fn notify_remote_edit(
+    doc: &mut AutoCommit,
+    old_heads: &[ChangeHash],
+    sender: NodeId,
+    changed_lines: ChangedLines,
+) {
+    if changed_lines.is_empty() {
+        return;
+    }
+    let Some(file_name) = ACTIVE_FILE.read().clone() else {
+        return;
+    };
+
+    *REMOTE_EDIT.write() = Some(RemoteEdit {
+        file_name,
+        authors: change_authors(doc, old_heads, sender),
+        lines: changed_lines.ranges().to_vec(),
+        received_at_millis: Local::now().timestamp_millis(),
+    });
+}
+
+/// Splices the replacements of all matches into the text objects, without committing them.
+///
+/// Returns the recorded splices of every changed file.
+fn splice_replacements(
+    doc: &mut AutoCommit,
+    query: &SearchQuery,
+    replacement: &str,
+) -> Result<ChangedFiles> {
+    let mut changed_files = Vec::new();
+
+    for file_name in files(doc)? {
+        let object_id = file_object(doc, &file_name)?;
+        let splices = query.splices(&doc.text(&object_id)?, replacement)?;
+        if splices.is_empty() {
+            continue;
+        }
+
+        let recorded_splices = apply_splices(doc, &object_id, &splices)?;
+        changed_files.push((file_name, recorded_splices));
+    }
+    Ok(changed_files)
+}
+
+/// Replaces all matches in all files as a single change, so peers receive it atomically.
+fn replace_all(
+    doc: &mut AutoCommit,
+    undo_histories: &mut HashMap<String, UndoHistory>,
+    query: &SearchQuery,
+    replacement: &str,
+    message: Option<String>,
+) -> Result<()> {
+    set_node_actor(doc);
+    let changed_files = match splice_replacements(doc, query, replacement) {
+        Ok(changed_files) => changed_files,
+        Err(error) => {
+            doc.rollback();
+            return Err(error);
+        }
+    };
+    if changed_files.is_empty() {
+        return Ok(());
+    }
+
+    let message = message.unwrap_or_else(|| {
+        format!(
+            "Replace '{}' with '{replacement}' in {} file(s)",
+            query.pattern,
+            changed_files.len()
+        )
+    });
+    let change_hash = commit_local_change(doc, message.clone());
+
+    let replacement_count = changed_files
+        .iter()
+        .map(|(_, recorded_splices)| recorded_splices.len())
+        .sum();
+    record_local_edits(undo_histories, change_hash, changed_files);
+
+    log_event(AutomergeEvent::ReplacedText {
+        message,
+        replacement_count,
+    });
+    Ok(())
+}
+
+/// Undoes or redoes the latest local edit of a file, keeping the changes of peers.
+fn revert_local_edit(
+    doc: &mut AutoCommit,
+    undo_histories: &mut HashMap<String, UndoHistory>,
+    file_name: &str,
+    direction: UndoDirection,
+) -> Result<()> {
+    let Some(undo_history) = undo_histories.get_mut(file_name) else {
+        return Ok(());
+    };
+    let Some(edit) = undo_history.pop(direction) else {
+        return Ok(());
+    };
+    set_node_actor(doc);
+
+    let object_id = file_object(doc, file_name)?;
+    let splices = edit.inverse_splices(doc, &object_id)?;
+    let recorded_splices = match apply_splices(doc, &object_id, &splices) {
+        Ok(recorded_splices) => recorded_splices,
+        Err(error) => {
+            doc.rollback();
+            return Err(error);
+        }
+    };
+
+    let change_hash = if splices.is_empty() {
+        // Peers already reverted the edit, an empty change would only clutter the history.
+        None
+    } else {
+        let reverted_change = edit
+            .change_hash
+            .map(|change_hash| change_hash.to_string()[..7].to_owned())
+            .unwrap_or_else(|| "local edit".to_owned());
+        let message = match direction {
+            UndoDirection::Undo => format!("Undo {reverted_change} in '{file_name}'"),
+            UndoDirection::Redo => format!("Redo {reverted_change} in '{file_name}'"),
+        };
+        commit_local_change(doc, message)
+    };
+    undo_history.push_reverted(direction, LocalEdit::new(change_hash, recorded_splices));
+    update_undo_depth(undo_histories, file_name);
+
+    log_event(AutomergeEvent::RevertedLocalEdit {
+        direction,
+        file_name: file_name.to_owned(),
+    });
+    Ok(())
+}
+
+/// Stores an uploaded file without committing, as text if it is UTF-8 and as bytes otherwise.
+///
+/// Returns the recorded splices of a text file, and whether the file got a new object which
+/// makes earlier local edits of it impossible to undo.
+fn store_uploaded_file(
+    doc: &mut AutoCommit,
+    file_name: &str,
+    content: Vec<u8>,
+) -> Result<(Vec<RecordedSplice>, bool)> {
+    let schema = DocumentSchema::read(doc, None)?;
+    let content = match String::from_utf8(content) {
+        Ok(text) if !text.contains('\0') => {
+            // Splice the difference into an existing text, so cursors of peers stay in place.
+            if let Ok(object_id) = schema.file(doc, file_name, None) {
+                let splices = splices_between(&doc.text(&object_id)?, &text);
+                return Ok((apply_splices(doc, &object_id, &splices)?, false));
+            }
+
+            let object_id = doc.put_object(&schema.files, file_name, ObjType::Text)?;
+            let splice = TextSplice {
+                index: 0,
+                deleted: String::new(),
+                inserted: text,
+            };
+            return Ok((apply_splices(doc, &object_id, &[splice])?, true));
+        }
+        Ok(text) => text.into_bytes(),
+        Err(error) => error.into_bytes(),
+    };
+
+    doc.put(&schema.files, file_name, ScalarValue::Bytes(content))?;
+    Ok((Vec::new(), true))
+}
+
+fn upload_file(
+    doc: &mut AutoCommit,
+    undo_histories: &mut HashMap<String, UndoHistory>,
+    file_name: &str,
+    content: Vec<u8>,
+    message: Option<String>,
+) -> Result<()> {
+    if file_name.is_empty() {
+        bail!("uploaded files need a name!")
+    }
+
+    let size = content.len();
+    set_node_actor(doc);
+    let (recorded_splices, replaced) = match store_uploaded_file(doc, file_name, content) {
+        Ok(result) => result,
+        Err(error) => {
+            doc.rollback();
+            return Err(error);
+        }
+    };
+    if recorded_splices.is_empty() && !replaced {
+        return Ok(());
+    }
+
+    let message = message.unwrap_or_else(|| format!("Upload '{file_name}'"));
+    let change_hash = commit_local_change(doc, message);
+    if replaced {
+        undo_histories.remove(file_name);
+        update_undo_depth(undo_histories, file_name);
+    }
+    if !recorded_splices.is_empty() {
+        record_local_edits(
+            undo_histories,
+            change_hash,
+            vec![(file_name.to_owned(), recorded_splices)],
+        );
+    }
+
+    log_event(AutomergeEvent::UploadedFile {
+        file_name: file_name.to_owned(),
+        size,
+    });
+    Ok(())
+}
+
+fn find_snapshot(doc: &AutoCommit, name: &str) -> Result<Snapshot> {
+    let Some(snapshot) = SNAPSHOTS
+        .read()
+        .iter()
+        .find(|snapshot| snapshot.name == name)
+        .cloned()
+    else {
+        bail!("no snapshot '{name}' found!")
+    };
+
+    if !doc.get_missing_deps(&snapshot.heads).is_empty() {
+        bail!("snapshot '{name}' contains changes which are missing in this document!")
+    }
+    Ok(snapshot)
+}
+
+fn create_snapshot(doc: &mut AutoCommit, name: &str) -> Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("snapshots need a name!")
+    }
+    if SNAPSHOTS
+        .read()
+        .iter()
+        .any(|snapshot| snapshot.name == name)
+    {
+        bail!("a snapshot named '{name}' already exists!")
+    }
+
+    let mut snapshots = SNAPSHOTS.write();
+    snapshots.push(Snapshot {
+        name: name.to_owned(),
+        created_at_millis: Local::now().timestamp_millis(),
+        heads: doc.get_heads(),
+    });
+    store_snapshots(&snapshots)?;
+    log_event(AutomergeEvent::CreatedSnapshot {
+        name: name.to_owned(),
+    });
+    Ok(())
+}
+
+fn delete_snapshot(name: &str) -> Result<()> {
+    let mut snapshots = SNAPSHOTS.write();
+    snapshots.retain(|snapshot| snapshot.name != name);
+    store_snapshots(&snapshots)?;
+
+    let mut snapshot_comparison = SNAPSHOT_COMPARISON.write();
+    if snapshot_comparison
+        .as_ref()
+        .is_some_and(|comparison| comparison.snapshot_name == name)
+    {
+        *snapshot_comparison = None;
+    }
+    Ok(())
+}
+
+fn compare_with_snapshot(doc: &mut AutoCommit, name: &str) -> Result<()> {
+    let snapshot = find_snapshot(doc, name)?;
+    let snapshot_files = file_contents_at(doc, &snapshot.heads)?;
+    let current_heads = doc.get_heads();
+    let current_files = file_contents_at(doc, &current_heads)?;
+
+    let file_names: BTreeSet<&String> = snapshot_files.keys().chain(current_files.keys()).collect();
+    let files = file_names
+        .into_iter()
+        .filter_map(|file_name| {
+            FileComparison::new(
+                file_name,
+                snapshot_files.get(file_name).map(String::as_str),
+                current_files.get(file_name).map(String::as_str),
+            )
+        })
+        .collect();
+
+    *SNAPSHOT_COMPARISON.write() = Some(SnapshotComparison {
+        snapshot_name: snapshot.name,
+        files,
+    });
+    Ok(())
+}
+
+/// Splices files back to their content in a snapshot, without committing.
+///
+/// Returns the recorded splices of every changed file and the names of files which were deleted
+/// or recreated with a new text object.
+fn splice_restored_files(
+    doc: &mut AutoCommit,
+    file_names: &[String],
+    snapshot_files: &BTreeMap<String, String>,
+    current_files: &BTreeMap<String, String>,
+) -> Result<(ChangedFiles, Vec<String>)> {
+    let files_object = files_object(doc)?;
+    let mut changed_files = Vec::new();
+    let mut replaced_file_names = Vec::new();
+
+    for file_name in file_names {
+        match (snapshot_files.get(file_name), current_files.get(file_name)) {
+            (Some(snapshot_content), Some(current_content)) => {
+                let splices = splices_between(current_content, snapshot_content);
+                if splices.is_empty() {
+                    continue;
+                }
+                let object_id = file_object(doc, file_name)?;
+                let recorded_splices = apply_splices(doc, &object_id, &splices)?;
+                changed_files.push((file_name.clone(), recorded_splices));
+            }
+            (Some(snapshot_content), None) => {
+                let object_id = doc.put_object(&files_object, file_name.as_str(), ObjType::Text)?;
+                let splice = TextSplice {
+                    index: 0,
+                    deleted: String::new(),
+                    inserted: snapshot_content.clone(),
+                };
+                let recorded_splices = apply_splices(doc, &object_id, &[splice])?;
+                changed_files.push((file_name.clone(), recorded_splices));
+                replaced_file_names.push(file_name.clone());
+            }
+            (None, Some(_)) => {
+                doc.delete(&files_object, file_name.as_str())?;
+                replaced_file_names.push(file_name.clone());
+            }
+            (None, None) => bail!("no file '{file_name}' found!"),
+        }
+    }
+    Ok((changed_files, replaced_file_names))
+}
+
+/// Restores one or all files to their content in a snapshot as a new change, so the history
+/// stays intact and peers receive the restore like any other edit.
+fn restore_snapshot(
+    doc: &mut AutoCommit,
+    undo_histories: &mut HashMap<String, UndoHistory>,
+    name: &str,
+    file_name: Option<&str>,
+) -> Result<()> {
+    let snapshot = find_snapshot(doc, name)?;
+    let snapshot_files = file_contents_at(doc, &snapshot.heads)?;
+    let current_heads = doc.get_heads();
+    let current_files = file_contents_at(doc, &current_heads)?;
+
+    let file_names: Vec<String> = match file_name {
+        Some(file_name) => vec![file_name.to_owned()],
+        None => snapshot_files
+            .keys()
+            .chain(current_files.keys())
+            .cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect(),
+    };
+
+    set_node_actor(doc);
+    let (changed_files, replaced_file_names) =
+        match splice_restored_files(doc, &file_names, &snapshot_files, &current_files) {
+            Ok(result) => result,
+            Err(error) => {
+                doc.rollback();
+                return Err(error);
+            }
+        };
+    if changed_files.is_empty() && replaced_file_names.is_empty() {
+        return Ok(());
+    }
+
+    let message = match file_name {
+        Some(file_name) => format!("Restore '{file_name}' to snapshot '{name}'"),
+        None => format!("Restore snapshot '{name}'"),
+    };
+    let change_hash = commit_local_change(doc, message.clone());
+
+    // The edits recorded for the old text objects can't be undone anymore.
+    for file_name in &replaced_file_names {
+        undo_histories.remove(file_name);
+        update_undo_depth(undo_histories, file_name);
+    }
+    record_local_edits(undo_histories, change_hash, changed_files);
+
+    log_event(AutomergeEvent::RestoredSnapshot { message });
+    Ok(())
+}
+
+/// Rebuilds the document from its saved form, so memory fragmented during a long session is
+/// released. Skipped if nothing changed since the last compaction.
+fn compact_document(doc: &mut AutoCommit, compacted_heads: &mut Vec<ChangeHash>) -> Result<()> {
+    let heads = doc.get_heads();
+    if heads == *compacted_heads {
+        return Ok(());
+    }
+
+    let saved = doc.save();
+    *doc = AutoCommit::load(&saved)?;
+    *compacted_heads = heads;
+    log_event(AutomergeEvent::CompactedDocument { size: saved.len() });
+    Ok(())
+}
+
+/// Refreshes the [`DocumentStatistics`] of the [`DIAGNOSTICS`], which requires saving the
+/// whole document.
+fn refresh_document_statistics(doc: &mut AutoCommit) -> Result<()> {
+    let changes = doc.get_changes(&[]);
+    let actor_count = changes
+        .iter()
+        .map(|change| change.actor_id())
+        .collect::<BTreeSet<_>>()
+        .len();
+    let change_count = changes.len();
+
+    let schema = DocumentSchema::read(doc, None)?;
+    let mut text_length = 0;
+    for file_name in schema.file_names(doc, None) {
+        text_length += doc.length(schema.file(doc, &file_name, None)?);
+    }
+
+    DIAGNOSTICS.write().document = Some(DocumentStatistics {
+        saved_bytes: doc.save().len(),
+        change_count,
+        actor_count,
+        file_count: FILES.read().len() + BINARY_FILES.read().len(),
+        text_length,
+    });
+    Ok(())
+}
+
+fn send_sync_messages(
+    doc: &mut AutoCommit,
+    states: &mut HashMap<NodeId, State>,
+    remote_node_id: NodeId,
+    connection_service: Coroutine<ConnectionCommand>,
+) -> Result<()> {
+    let state = states.entry(remote_node_id).or_default();
+    while let Some(message) = doc.sync().generate_sync_message(state) {
+        let details = MessageDetails::from_message(&message, None)?;
+        log_event(AutomergeEvent::CreatedSyncMessage { details });
+        connection_service.send(ConnectionCommand::SendMessage {
+            remote_node_id,
+            message,
+        });
+    }
+    Ok(())
+}
+
+/// Updates the files from the changes made since `old_heads` and sends them to all peers.
+fn publish_local_changes(
+    doc: &mut AutoCommit,
+    states: &mut HashMap<NodeId, State>,
+    old_heads: &[ChangeHash],
+    connection_service: Coroutine<ConnectionCommand>,
+) -> Result<()> {
+    update_files_since(doc, old_heads)?;
+
+    let remote_node_ids: Vec<NodeId> = CONNECTED_PEERS
+        .read()
+        .iter()
+        .map(|peer| peer.node_id)
+        .collect();
+    for remote_node_id in remote_node_ids {
+        send_sync_messages(doc, states, remote_node_id, connection_service)?;
+        update_sync_status(doc, states, remote_node_id);
+    }
     Ok(())
 }
 
 pub enum AutomergeCommand {
-    ApplyMessage { message: AutomergeSyncMessage },
-    SelectFile { file_name: String },
-    StartSync,
+    ApplyMessage {
+        remote_node_id: NodeId,
+        role: PeerRole,
+        message: AutomergeSyncMessage,
+        /// Length of the encoded message as received.
+        size: usize,
+    },
+    /// Reads all files into the search index, which is put off until the first search.
+    BuildSearchIndex,
+    CloseFile {
+        file_name: String,
+    },
+    CompactDocument,
+    CompareWithSnapshot {
+        name: String,
+    },
+    /// Tags the current heads with a name.
+    CreateSnapshot {
+        name: String,
+    },
+    DeleteSnapshot {
+        name: String,
+    },
+    /// Drops the sync state of a peer which disconnected.
+    ForgetPeer {
+        remote_node_id: NodeId,
+    },
+    /// Redoes the latest undone local edit of a file.
+    Redo {
+        file_name: String,
+    },
+    /// Computes the document statistics, which takes too long to do on every change.
+    RefreshDiagnostics,
+    /// Replaces all matches of the query in all files with a single commit.
+    ReplaceAll {
+        query: SearchQuery,
+        replacement: String,
+        /// Describes the commit instead of the generated message.
+        message: Option<String>,
+    },
+    /// Replays a recorded session into a separate document and shows its files, leaving the live
+    /// document untouched.
+    ReplayRecording {
+        recording: Box<SessionRecording>,
+    },
+    /// Forgets what is known about the peer, so the next sync starts from scratch.
+    ResetSync {
+        remote_node_id: NodeId,
+    },
+    /// Restores one or, without a file name, all files to their content in a snapshot.
+    RestoreSnapshot {
+        name: String,
+        file_name: Option<String>,
+    },
+    /// Loads a binary file for preview and download.
+    SelectBinaryFile {
+        file_name: String,
+    },
+    /// Opens the file in a tab, or activates its tab if it is already open.
+    SelectFile {
+        file_name: String,
+    },
+    /// Shows who last wrote each line of a file, or hides the blame without a file name.
+    ShowBlame {
+        file_name: Option<String>,
+    },
+    /// Records the frames of all connections, starting from the current document.
+    StartRecording,
+    StartSync {
+        remote_node_id: NodeId,
+    },
+    /// Undoes the latest local edit of a file, without touching the changes of peers.
+    Undo {
+        file_name: String,
+    },
+    /// Stores a file from disk, replacing a file of the same name.
+    UploadFile {
+        file_name: String,
+        content: Vec<u8>,
+        /// Describes the commit instead of the generated message.
+        message: Option<String>,
+    },
+}
+
+/// Removes the changes of a viewer from its sync message, along with all references to them.
+///
+/// Otherwise we would keep asking the viewer for its heads, and reset the sync because we lack
+/// the heads it last synced. As its changes build on what we sent it, our last sent heads stand
+/// in for its unknown heads. The rest of the message is kept, so the viewer still receives our
+/// changes.
+fn refuse_changes(doc: &mut AutoCommit, state: &State, message: &mut AutomergeSyncMessage) {
+    message.changes = ChunkList::empty();
+
+    let heads_count = message.heads.len();
+    message
+        .heads
+        .retain(|head| doc.get_change_by_hash(head).is_some());
+    if message.heads.len() < heads_count {
+        message.heads.extend(state.last_sent_heads.iter().copied());
+        message.heads.sort();
+        message.heads.dedup();
+    }
+
+    for have in &mut message.have {
+        have.last_sync
+            .retain(|hash| doc.get_change_by_hash(hash).is_some());
+    }
+}
+
+/// Applies a sync message from a peer and returns the events to log.
+///
+/// Doesn't touch any signals, so it can also be used outside of the Dioxus runtime.
+async fn receive_message(
+    doc: &mut AutoCommit,
+    states: &mut HashMap<NodeId, State>,
+    remote_node_id: NodeId,
+    role: PeerRole,
+    mut message: AutomergeSyncMessage,
+    size: usize,
+) -> Result<Vec<AutomergeEvent>> {
+    let mut events = Vec::new();
+    let state = states.entry(remote_node_id).or_default();
+    if role == PeerRole::Viewer {
+        if !message.changes.is_empty() {
+            events.push(AutomergeEvent::RefusedChanges {
+                remote_node_id,
+                change_count: message.changes.len(),
+            });
+        }
+        refuse_changes(doc, state, &mut message);
+    }
+
+    let details = MessageDetails::from_message(&message, Some(size))?;
+    let duration = apply_message(doc, state, message).await?;
+    events.push(AutomergeEvent::AppliedSyncMessage { details, duration });
+    Ok(events)
+}
+
+/// Feeds the sync messages of a recording into a separate copy of the document it started from.
+///
+/// Local changes of the session, e.g. from replacing text or undoing, only exist in the outgoing
+/// frames, so their changes are applied as well. Like [`receive_message`] this doesn't depend on
+/// the Dioxus runtime, so recordings can drive regression tests.
+pub async fn replay_recording(recording: &SessionRecording) -> Result<AutoCommit> {
+    let mut doc = AutoCommit::load(&recording.initial_doc)?;
+    let mut states: HashMap<NodeId, State> = HashMap::new();
+
+    for frame in &recording.frames {
+        let PeerMessage::Sync(message_buf) = frame.peer_message()? else {
+            continue;
+        };
+        let message = AutomergeSyncMessage::decode(&message_buf)?;
+
+        match frame.direction {
+            FrameDirection::Incoming => {
+                receive_message(
+                    &mut doc,
+                    &mut states,
+                    frame.remote_node_id,
+                    frame.role,
+                    message,
+                    message_buf.len(),
+                )
+                .await?;
+            }
+            FrameDirection::Outgoing => {
+                // Like generating the message did, which refusing changes of viewers relies on.
+                states
+                    .entry(frame.remote_node_id)
+                    .or_default()
+                    .last_sent_heads
+                    .clone_from(&message.heads);
+                // Changes we already have are skipped, a throwaway state leaves the peer's alone.
+                doc.sync()
+                    .receive_sync_message(&mut State::new(), message)?;
+            }
+        }
+    }
+
+    Ok(doc)
 }
 
 async fn handle_automerge_command(
     doc: &mut AutoCommit,
-    state: &mut State,
+    states: &mut HashMap<NodeId, State>,
+    undo_histories: &mut HashMap<String, UndoHistory>,
+    compacted_heads: &mut Vec<ChangeHash>,
     command: AutomergeCommand,
     connection_service: Coroutine<ConnectionCommand>,
 ) -> Result<()> {
     match command {
-        AutomergeCommand::ApplyMessage { message } => {
-            let details = MessageDetails::from_message(&message)?;
-            apply_message(doc, state, message).await?;
-            AUTOMERGE_EVENTS
-                .write()
-                .push(AutomergeEvent::AppliedSyncMessage {
-                    date_time: Local::now(),
-                    details,
-                });
-
-            *FILES.write() = files(doc)?;
-
-            if let Some(previous_selected_file) = SELECTED_FILE.read().as_ref() {
-                let file_name = &previous_selected_file.file_name;
-                if FILES.read().contains(file_name) {
-                    select_file(doc, file_name)?;
-                } else {
-                    *SELECTED_FILE.write() = None;
+        AutomergeCommand::ApplyMessage {
+            remote_node_id,
+            role,
+            message,
+            size,
+        } => {
+            let old_heads = doc.get_heads();
+            let events = receive_message(doc, states, remote_node_id, role, message, size).await?;
+            for event in events {
+                if let AutomergeEvent::AppliedSyncMessage { duration, .. } = &event {
+                    record_received_message(size, *duration);
                 }
+                log_event(event);
+            }
+            // Answer right away, the peer may wait for our heads or changes it needs.
+            send_sync_messages(doc, states, remote_node_id, connection_service)?;
+            update_sync_status(doc, states, remote_node_id);
+
+            let changed_lines = update_files_since(doc, &old_heads)?;
+            notify_remote_edit(doc, &old_heads, remote_node_id, changed_lines);
+        }
+        AutomergeCommand::RefreshDiagnostics => {
+            refresh_document_statistics(doc)?;
+        }
+        AutomergeCommand::ReplaceAll {
+            ref query,
+            ref replacement,
+            message,
+        } => {
+            let old_heads = doc.get_heads();
+            replace_all(doc, undo_histories, query, replacement, message)?;
+            publish_local_changes(doc, states, &old_heads, connection_service)?;
+        }
+        AutomergeCommand::Redo { ref file_name } => {
+            let old_heads = doc.get_heads();
+            revert_local_edit(doc, undo_histories, file_name, UndoDirection::Redo)?;
+            publish_local_changes(doc, states, &old_heads, connection_service)?;
+        }
+        AutomergeCommand::ReplayRecording { recording } => {
+            let mut replayed_doc = replay_recording(&recording).await?;
+            let heads = replayed_doc.get_heads();
+            *REPLAYED_RECORDING.write() = Some(ReplayedRecording {
+                frame_count: recording.frames.len(),
+                file_contents: file_contents_at(&replayed_doc, &heads)?,
+            });
+            log_event(AutomergeEvent::ReplayedRecording {
+                frame_count: recording.frames.len(),
+            });
+        }
+        AutomergeCommand::BuildSearchIndex => {
+            build_search_index(doc)?;
+        }
+        AutomergeCommand::CloseFile { ref file_name } => {
+            close_file(file_name);
+        }
+        AutomergeCommand::CompactDocument => {
+            compact_document(doc, compacted_heads)?;
+        }
+        AutomergeCommand::CompareWithSnapshot { ref name } => {
+            compare_with_snapshot(doc, name)?;
+        }
+        AutomergeCommand::CreateSnapshot { ref name } => {
+            create_snapshot(doc, name)?;
+        }
+        AutomergeCommand::DeleteSnapshot { ref name } => {
+            delete_snapshot(name)?;
+        }
+        AutomergeCommand::ForgetPeer { remote_node_id } => {
+            states.remove(&remote_node_id);
+            SYNC_STATUS.write().remove(&remote_node_id);
+        }
+        AutomergeCommand::ResetSync { remote_node_id } => {
+            states.insert(remote_node_id, SyncState::new());
+            update_sync_status(doc, states, remote_node_id);
+        }
+        AutomergeCommand::RestoreSnapshot {
+            ref name,
+            ref file_name,
+        } => {
+            let old_heads = doc.get_heads();
+            restore_snapshot(doc, undo_histories, name, file_name.as_deref())?;
+            publish_local_changes(doc, states, &old_heads, connection_service)?;
+
+            let is_compared = SNAPSHOT_COMPARISON
+                .read()
+                .as_ref()
+                .is_some_and(|comparison| comparison.snapshot_name == *name);
+            if is_compared {
+                compare_with_snapshot(doc, name)?;
             }
         }
+        AutomergeCommand::SelectBinaryFile { ref file_name } => {
+            select_binary_file(doc, file_name)?;
+        }
         AutomergeCommand::SelectFile { ref file_name } => {
             select_file(doc, file_name)?;
         }
-        AutomergeCommand::StartSync => {
-            while let Some(message) = doc.sync().generate_sync_message(state) {
-                let details = MessageDetails::from_message(&message)?;
-                AUTOMERGE_EVENTS
-                    .write()
-                    .push(AutomergeEvent::CreatedSyncMessage {
-                        date_time: Local::now(),
-                        details,
-                    });
-                connection_service.send(ConnectionCommand::SendMessage { message });
-            }
+        AutomergeCommand::ShowBlame { file_name } => {
+            *FILE_BLAME.write() = match file_name {
+                Some(file_name) => Some(blame_file(doc, &file_name)?),
+                None => None,
+            };
+        }
+        AutomergeCommand::StartRecording => {
+            *SESSION_RECORDING.write() = Some(SessionRecording::start(doc.save()));
+        }
+        AutomergeCommand::StartSync { remote_node_id } => {
+            send_sync_messages(doc, states, remote_node_id, connection_service)?;
+        }
+        AutomergeCommand::Undo { ref file_name } => {
+            let old_heads = doc.get_heads();
+            revert_local_edit(doc, undo_histories, file_name, UndoDirection::Undo)?;
+            publish_local_changes(doc, states, &old_heads, connection_service)?;
+        }
+        AutomergeCommand::UploadFile {
+            ref file_name,
+            content,
+            message,
+        } => {
+            let old_heads = doc.get_heads();
+            upload_file(doc, undo_histories, file_name, content, message)?;
+            publish_local_changes(doc, states, &old_heads, connection_service)?;
         }
     }
     Ok(())
 }
 
 fn handle_error(error: Error) {
-    AUTOMERGE_EVENTS.write().push(AutomergeEvent::Error {
-        date_time: Local::now(),
-        error,
-    });
+    match error.downcast::<SchemaMismatch>() {
+        Ok(mismatch) => log_event(AutomergeEvent::SchemaMismatch { mismatch }),
+        Err(error) => log_event(AutomergeEvent::Error { error }),
+    }
 }
 
 pub async fn start_automerge_service(mut commands_rx: UnboundedReceiver<AutomergeCommand>) {
     let connection_service = use_coroutine_handle::<ConnectionCommand>();
 
-    // TODO: load content from local storage?
-    // see https://github.com/ethersync/ethersync/blob/v0.7.0/daemon/src/document.rs#L37
-    let initial_doc = [
-        133, 111, 74, 131, 61, 157, 231, 85, 0, 118, 1, 16, 120, 107, 104, 47, 215, 9, 76, 32, 132,
-        136, 60, 124, 152, 120, 144, 182, 1, 143, 164, 31, 13, 102, 61, 139, 125, 246, 189, 135,
-        97, 16, 167, 63, 30, 215, 249, 60, 227, 113, 111, 61, 55, 138, 234, 94, 30, 142, 166, 78,
-        250, 6, 1, 2, 3, 2, 19, 2, 35, 2, 64, 2, 86, 2, 7, 21, 14, 33, 2, 35, 2, 52, 1, 66, 2, 86,
-        2, 128, 1, 2, 127, 0, 127, 1, 127, 2, 127, 0, 127, 0, 127, 7, 126, 5, 102, 105, 108, 101,
-        115, 6, 115, 116, 97, 116, 101, 115, 2, 0, 2, 1, 2, 2, 0, 2, 0, 2, 0, 0,
-    ];
-    let maybe_doc = AutoCommit::load(&initial_doc);
-    if let Err(error) = maybe_doc {
-        handle_error(anyhow!(error));
-        return;
+    let mut doc = match load_initial_doc() {
+        Ok(doc) => doc,
+        Err(error) => {
+            handle_error(error);
+            return;
+        }
+    };
+    if let Err(error) = check_schema(&doc) {
+        handle_error(error);
     }
+    let mut states = HashMap::new();
+    let mut undo_histories = HashMap::new();
+    let mut compacted_heads = doc.get_heads();
 
-    let mut doc = maybe_doc.unwrap();
-    let mut state = SyncState::default();
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    spawn(async move {
+        loop {
+            sleep(COMPACTION_INTERVAL).await;
+            automerge_service.send(AutomergeCommand::CompactDocument);
+        }
+    });
+
+    match load_snapshots().await {
+        Ok(snapshots) => *SNAPSHOTS.write() = snapshots,
+        Err(error) => handle_error(error),
+    }
 
     while let Some(command) = commands_rx.next().await {
-        if let Err(error) =
-            handle_automerge_command(&mut doc, &mut state, command, connection_service).await
+        if let Err(error) = handle_automerge_command(
+            &mut doc,
+            &mut states,
+            &mut undo_histories,
+            &mut compacted_heads,
+            command,
+            connection_service,
+        )
+        .await
         {
             handle_error(error);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::session_recorder::RecordedFrame;
+    use async_std::task::block_on;
+    use iroh::SecretKey;
+    use postcard::to_allocvec;
+
+    fn random_node_id() -> NodeId {
+        SecretKey::generate(rand::thread_rng()).public()
+    }
+
+    #[test]
+    fn refused_viewer_changes_settle_the_sync() {
+        let mut doc = load_initial_doc().unwrap();
+        let mut viewer_doc = doc.fork();
+        doc.put(automerge::ROOT, "ours", 1).unwrap();
+        doc.commit();
+        viewer_doc.put(automerge::ROOT, "viewers", 2).unwrap();
+        viewer_doc.commit();
+
+        let viewer_node_id = random_node_id();
+        let mut states = HashMap::new();
+        let mut viewer_state = State::new();
+        let mut rounds = 0;
+        loop {
+            let our_message = doc
+                .sync()
+                .generate_sync_message(states.entry(viewer_node_id).or_default());
+            let viewer_message = viewer_doc.sync().generate_sync_message(&mut viewer_state);
+            if our_message.is_none() && viewer_message.is_none() {
+                break;
+            }
+
+            rounds += 1;
+            assert!(rounds < 10, "sync with the viewer doesn't settle");
+            if let Some(message) = our_message {
+                viewer_doc
+                    .sync()
+                    .receive_sync_message(&mut viewer_state, message)
+                    .unwrap();
+            }
+            if let Some(message) = viewer_message {
+                block_on(receive_message(
+                    &mut doc,
+                    &mut states,
+                    viewer_node_id,
+                    PeerRole::Viewer,
+                    message,
+                    0,
+                ))
+                .unwrap();
+            }
+        }
+
+        assert!(doc.get(automerge::ROOT, "viewers").unwrap().is_none());
+        assert!(viewer_doc.get(automerge::ROOT, "ours").unwrap().is_some());
+        assert_eq!(
+            SyncStatus::compute(&mut doc, &states[&viewer_node_id]),
+            SyncStatus::InSync
+        );
+    }
+
+    fn recorded_frame(
+        remote_node_id: NodeId,
+        direction: FrameDirection,
+        message: &AutomergeSyncMessage,
+    ) -> RecordedFrame {
+        RecordedFrame {
+            timestamp_millis: 0,
+            remote_node_id,
+            role: PeerRole::Editor,
+            direction,
+            frame: to_allocvec(&PeerMessage::Sync(message.clone().encode())).unwrap(),
+        }
+    }
+
+    /// Syncs with the peer until both sides are done, recording the frames on our side.
+    fn sync_recorded(
+        doc: &mut AutoCommit,
+        states: &mut HashMap<NodeId, State>,
+        peer_doc: &mut AutoCommit,
+        peer_state: &mut State,
+        peer_node_id: NodeId,
+        recording: &mut SessionRecording,
+    ) {
+        let mut rounds = 0;
+        loop {
+            let peer_message = peer_doc.sync().generate_sync_message(peer_state);
+            let our_message = doc
+                .sync()
+                .generate_sync_message(states.entry(peer_node_id).or_default());
+            if peer_message.is_none() && our_message.is_none() {
+                break;
+            }
+
+            rounds += 1;
+            assert!(rounds < 10, "sync with the peer doesn't settle");
+            if let Some(message) = our_message {
+                recording.frames.push(recorded_frame(
+                    peer_node_id,
+                    FrameDirection::Outgoing,
+                    &message,
+                ));
+                peer_doc
+                    .sync()
+                    .receive_sync_message(peer_state, message)
+                    .unwrap();
+            }
+            if let Some(message) = peer_message {
+                recording.frames.push(recorded_frame(
+                    peer_node_id,
+                    FrameDirection::Incoming,
+                    &message,
+                ));
+                block_on(receive_message(
+                    doc,
+                    states,
+                    peer_node_id,
+                    PeerRole::Editor,
+                    message,
+                    0,
+                ))
+                .unwrap();
+            }
+        }
+    }
+
+    fn replayed_file_contents(
+        recording: &SessionRecording,
+    ) -> (BTreeMap<String, String>, Vec<ChangeHash>) {
+        let recording = SessionRecording::decode(&recording.encode().unwrap()).unwrap();
+        let mut replayed_doc = block_on(replay_recording(&recording)).unwrap();
+        let heads = replayed_doc.get_heads();
+        let file_contents = file_contents_at(&replayed_doc, &heads).unwrap();
+        (file_contents, heads)
+    }
+
+    #[test]
+    fn replayed_recording_reproduces_the_files() {
+        let mut doc = load_initial_doc().unwrap();
+        let mut peer_doc = doc.fork();
+        let mut recording = SessionRecording::start(doc.save());
+
+        let files = files_object(&peer_doc).unwrap();
+        let object_id = peer_doc
+            .put_object(&files, "hello.txt", ObjType::Text)
+            .unwrap();
+        peer_doc
+            .splice_text(&object_id, 0, 0, "Hello, world")
+            .unwrap();
+        peer_doc.commit();
+
+        let peer_node_id = random_node_id();
+        let mut states = HashMap::new();
+        let mut peer_state = State::new();
+        sync_recorded(
+            &mut doc,
+            &mut states,
+            &mut peer_doc,
+            &mut peer_state,
+            peer_node_id,
+            &mut recording,
+        );
+
+        let (file_contents, heads) = replayed_file_contents(&recording);
+        assert_eq!(
+            file_contents,
+            BTreeMap::from([("hello.txt".to_string(), "Hello, world".to_string())])
+        );
+        assert_eq!(heads, doc.get_heads());
+    }
+
+    #[test]
+    fn replayed_recording_includes_local_changes() {
+        let mut doc = load_initial_doc().unwrap();
+        let mut peer_doc = doc.fork();
+        let mut recording = SessionRecording::start(doc.save());
+
+        let files = files_object(&peer_doc).unwrap();
+        let object_id = peer_doc
+            .put_object(&files, "hello.txt", ObjType::Text)
+            .unwrap();
+        peer_doc.splice_text(&object_id, 0, 0, "Hello").unwrap();
+        peer_doc.commit();
+
+        let peer_node_id = random_node_id();
+        let mut states = HashMap::new();
+        let mut peer_state = State::new();
+        let mut sync = |doc: &mut AutoCommit, peer_doc: &mut AutoCommit| {
+            sync_recorded(
+                doc,
+                &mut states,
+                peer_doc,
+                &mut peer_state,
+                peer_node_id,
+                &mut recording,
+            )
+        };
+        sync(&mut doc, &mut peer_doc);
+
+        // A local change, which only the outgoing frames carry.
+        doc.splice_text(&object_id, 5, 0, ", world").unwrap();
+        doc.commit();
+        sync(&mut doc, &mut peer_doc);
+
+        // A change of the peer depending on it.
+        peer_doc.splice_text(&object_id, 12, 0, "!").unwrap();
+        peer_doc.commit();
+        sync(&mut doc, &mut peer_doc);
+
+        let (file_contents, heads) = replayed_file_contents(&recording);
+        assert_eq!(
+            file_contents,
+            BTreeMap::from([("hello.txt".to_string(), "Hello, world!".to_string())])
+        );
+        assert_eq!(heads, doc.get_heads());
+    }
+}
@@ -1,52 +1,182 @@
 use crate::services::automerge_service::AutomergeCommand;
-use anyhow::{Context, Error, Result};
+use crate::services::diagnostics::record_sent_message;
+use crate::services::event_log::{log_event, LogEvent, LogLevel, LogSource};
+use crate::services::presence_service::{peer_name, PresenceCommand};
+use crate::services::session_recorder::{record_frame, FrameDirection};
+use anyhow::{anyhow, Error, Result};
+use async_std::task::sleep;
 use automerge::sync::Message as AutomergeSyncMessage;
+use automerge::ChangeHash;
 use chrono::{DateTime, Local};
 use derive_more::{Deref, Display};
 use dioxus::hooks::UnboundedReceiver;
 use dioxus::prelude::{spawn, use_coroutine_handle, Coroutine, GlobalSignal, Signal};
+use futures::channel::mpsc::{unbounded, UnboundedSender};
 use futures::StreamExt;
-use iroh::endpoint::{Connection, RecvStream, SendStream};
+use iroh::endpoint::{
+    Connection, ConnectionError, ConnectionType, RecvStream, SendStream, TransportErrorCode,
+};
+use iroh::watchable::Watcher;
 use iroh::NodeId;
 use postcard::{from_bytes, to_allocvec};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
-use tokio::sync::broadcast;
-use tokio::sync::broadcast::{Receiver, Sender};
+use std::time::Duration;
 
-pub static CONNECTED_PEERS: GlobalSignal<Vec<NodeId>> = Signal::global(Vec::new);
+const PEER_DETAILS_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// TLS alert closing the handshake when both sides have no ALPN in common.
+const NO_APPLICATION_PROTOCOL_ALERT: u8 = 120;
+
+/// Version of the peer protocol, negotiated through the ALPN of the connection.
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum ProtocolVersion {
+    #[display("/ethersync/0")]
+    Ethersync0,
+}
+
+impl ProtocolVersion {
+    /// Versions we speak, from the most to the least preferred.
+    pub const SUPPORTED: [ProtocolVersion; 1] = [ProtocolVersion::Ethersync0];
+
+    pub fn alpn(self) -> &'static [u8] {
+        match self {
+            ProtocolVersion::Ethersync0 => b"/ethersync/0",
+        }
+    }
+
+    pub fn supported_alpns() -> Vec<Vec<u8>> {
+        Self::SUPPORTED
+            .iter()
+            .map(|version| version.alpn().to_vec())
+            .collect()
+    }
+
+    /// Returns the version negotiated for an established connection.
+    pub fn negotiated(connection: &Connection) -> Result<Self> {
+        Self::from_alpn(&connection.alpn().unwrap_or_default())
+    }
+
+    fn from_alpn(alpn: &[u8]) -> Result<Self> {
+        Self::SUPPORTED
+            .into_iter()
+            .find(|version| version.alpn() == alpn)
+            .ok_or_else(|| {
+                anyhow!(
+                    "peer negotiated unknown protocol '{}'!",
+                    String::from_utf8_lossy(alpn)
+                )
+            })
+    }
+}
+
+/// Whether a handshake failed because the peer supports none of our protocol versions.
+pub fn is_protocol_mismatch(error: &ConnectionError) -> bool {
+    let mismatch_code = TransportErrorCode::crypto(NO_APPLICATION_PROTOCOL_ALERT);
+    match error {
+        ConnectionError::TransportError(error) => error.code == mismatch_code,
+        ConnectionError::ConnectionClosed(close) => close.error_code == mismatch_code,
+        _ => false,
+    }
+}
+
+/// What an authenticated peer is allowed to do with the shared document.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Serialize, Deserialize)]
+pub enum PeerRole {
+    /// Changes of the peer are applied to the document.
+    #[display("editor")]
+    Editor,
+    /// The peer receives sync messages, but its changes are refused.
+    #[display("viewer")]
+    Viewer,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ConnectedPeer {
+    pub node_id: NodeId,
+    pub role: PeerRole,
+    pub protocol_version: ProtocolVersion,
+    pub connected_at: DateTime<Local>,
+    /// Direct address and/or relay used to reach the peer.
+    pub connection_type: Option<ConnectionType>,
+    pub round_trip_time: Duration,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub messages_received: usize,
+    pub messages_sent: usize,
+    /// Heads announced by the peer in its most recent sync message.
+    pub last_sync_heads: Vec<ChangeHash>,
+}
+
+pub static CONNECTED_PEERS: GlobalSignal<Vec<ConnectedPeer>> = Signal::global(Vec::new);
+
+fn update_connected_peer(remote_node_id: NodeId, update: impl FnOnce(&mut ConnectedPeer)) {
+    if let Some(peer) = CONNECTED_PEERS
+        .write()
+        .iter_mut()
+        .find(|peer| peer.node_id == remote_node_id)
+    {
+        update(peer);
+    }
+}
 
 pub enum ConnectionCommand {
+    /// Forgets a peer once reading from its connection stopped.
+    ConnectionClosed {
+        remote_node_id: NodeId,
+        connection_id: usize,
+    },
+    Disconnect {
+        remote_node_id: NodeId,
+    },
     NewConnection {
         connection: Connection,
+        connection_type: Option<Watcher<ConnectionType>>,
         receive: RecvStream,
         send: SendStream,
+        role: PeerRole,
+        protocol_version: ProtocolVersion,
+    },
+    SendEphemeralMessage {
+        remote_node_id: NodeId,
+        message: EphemeralMessage,
     },
     SendMessage {
+        remote_node_id: NodeId,
         message: AutomergeSyncMessage,
     },
 }
 
+/// Handles kept by the connection service for every connected peer.
+struct PeerConnection {
+    connection: Connection,
+    outgoing_message_tx: UnboundedSender<PeerMessage>,
+}
+
 pub enum ConnectionEvent {
     Connected {
-        date_time: DateTime<Local>,
         remote_node_id: NodeId,
+        role: PeerRole,
     },
     Disconnected {
-        date_time: DateTime<Local>,
         remote_node_id: NodeId,
     },
     Error {
-        date_time: DateTime<Local>,
         error: Error,
     },
+    /// The peer speaks a protocol version we don't understand, `peer_version` is unknown if the
+    /// connection failed during the handshake.
+    IncompatiblePeerVersion {
+        remote_node_id: Option<NodeId>,
+        peer_version: Option<ProtocolVersion>,
+        reason: String,
+    },
     IncomingPeerMessage {
-        date_time: DateTime<Local>,
         remote_node_id: NodeId,
         message_type: String,
     },
     OutgoingPeerMessage {
-        date_time: DateTime<Local>,
         remote_node_id: NodeId,
         message_type: String,
     },
@@ -56,46 +186,100 @@ impl Display for ConnectionEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ConnectionEvent::Connected {
-                date_time,
                 remote_node_id,
-            } => write!(f, "{date_time}: connected to {remote_node_id}"),
-            ConnectionEvent::Disconnected {
-                date_time,
+                role,
+            } => write!(f, "connected to {} as {role}", peer_name(*remote_node_id)),
+            ConnectionEvent::Disconnected { remote_node_id } => {
+                write!(f, "disconnected from {}", peer_name(*remote_node_id))
+            }
+            ConnectionEvent::Error { error } => {
+                write!(f, "connection error {error}")
+            }
+            ConnectionEvent::IncompatiblePeerVersion {
                 remote_node_id,
-            } => write!(f, "{date_time}: disconnected from {remote_node_id}"),
-            ConnectionEvent::Error { date_time, error } => {
-                write!(f, "{date_time}: connection error {error}")
+                peer_version,
+                reason,
+            } => {
+                match peer_version {
+                    Some(peer_version) => write!(f, "incompatible peer version {peer_version}")?,
+                    None => write!(f, "incompatible peer version")?,
+                }
+                if let Some(remote_node_id) = remote_node_id {
+                    write!(f, " of {}", peer_name(*remote_node_id))?;
+                }
+                write!(f, ": {reason}")
             }
             ConnectionEvent::IncomingPeerMessage {
-                date_time,
                 remote_node_id,
                 message_type,
             } => {
                 write!(
                     f,
-                    "{date_time}: received {message_type} message from {remote_node_id}"
+                    "received {message_type} message from {}",
+                    peer_name(*remote_node_id)
                 )
             }
             ConnectionEvent::OutgoingPeerMessage {
-                date_time,
                 remote_node_id,
                 message_type,
             } => {
                 write!(
                     f,
-                    "{date_time}: sent {message_type} message to {remote_node_id}"
+                    "sent {message_type} message to {}",
+                    peer_name(*remote_node_id)
                 )
             }
         }
     }
 }
 
-pub static CONNECTION_EVENTS: GlobalSignal<Vec<ConnectionEvent>> = Signal::global(Vec::new);
+impl LogEvent for ConnectionEvent {
+    fn level(&self) -> LogLevel {
+        match self {
+            ConnectionEvent::Connected { .. } | ConnectionEvent::Disconnected { .. } => {
+                LogLevel::Info
+            }
+            ConnectionEvent::Error { .. } | ConnectionEvent::IncompatiblePeerVersion { .. } => {
+                LogLevel::Error
+            }
+            ConnectionEvent::IncomingPeerMessage { .. }
+            | ConnectionEvent::OutgoingPeerMessage { .. } => LogLevel::Debug,
+        }
+    }
+
+    fn source(&self) -> LogSource {
+        LogSource::Connection
+    }
+
+    fn peer(&self) -> Option<NodeId> {
+        match self {
+            ConnectionEvent::Connected { remote_node_id, .. }
+            | ConnectionEvent::Disconnected { remote_node_id }
+            | ConnectionEvent::IncomingPeerMessage { remote_node_id, .. }
+            | ConnectionEvent::OutgoingPeerMessage { remote_node_id, .. } => Some(*remote_node_id),
+            ConnectionEvent::IncompatiblePeerVersion { remote_node_id, .. } => *remote_node_id,
+            ConnectionEvent::Error { .. } => None,
+        }
+    }
+}
 
 fn handle_error(error: Error) {
-    CONNECTION_EVENTS.write().push(ConnectionEvent::Error {
-        date_time: Local::now(),
-        error,
+    log_event(ConnectionEvent::Error { error });
+}
+
+/// Logs that the peer supports none of our protocol versions.
+pub fn log_protocol_mismatch(remote_node_id: Option<NodeId>) {
+    let supported_versions: Vec<String> = ProtocolVersion::SUPPORTED
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    log_event(ConnectionEvent::IncompatiblePeerVersion {
+        remote_node_id,
+        peer_version: None,
+        reason: format!(
+            "no protocol in common, we support {}",
+            supported_versions.join(", ")
+        ),
     });
 }
 
@@ -119,6 +303,12 @@ pub struct Range {
 #[must_use]
 pub struct RelativePath(PathBuf);
 
+impl RelativePath {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self(path.into())
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
 pub struct CursorState {
     pub name: Option<String>,
@@ -143,147 +333,462 @@ pub enum PeerMessage {
     Ephemeral(EphemeralMessage),
 }
 
-async fn receive_peer_message(receive: &mut RecvStream) -> Result<PeerMessage> {
+impl PeerMessage {
+    fn message_type(&self) -> &'static str {
+        match self {
+            PeerMessage::Sync(_) => "sync",
+            PeerMessage::Ephemeral(_) => "ephemeral",
+        }
+    }
+}
+
+async fn receive_frame(
+    remote_node_id: NodeId,
+    role: PeerRole,
+    receive: &mut RecvStream,
+) -> Result<Vec<u8>> {
     let mut message_len_buf = [0; 4];
     receive.read_exact(&mut message_len_buf).await?;
     let message_len = u32::from_be_bytes(message_len_buf);
 
     let mut message_buf = vec![0; message_len as usize];
     receive.read_exact(&mut message_buf).await?;
-
-    from_bytes(&message_buf).context("Failed to convert bytes to PeerMessage")
+    record_frame(remote_node_id, role, FrameDirection::Incoming, &message_buf);
+    Ok(message_buf)
 }
 
 fn handle_peer_message(
     remote_node_id: NodeId,
+    role: PeerRole,
     peer_message: PeerMessage,
     automerge_service: Coroutine<AutomergeCommand>,
+    presence_service: Coroutine<PresenceCommand>,
 ) -> Result<()> {
+    log_event(ConnectionEvent::IncomingPeerMessage {
+        remote_node_id,
+        message_type: peer_message.message_type().to_string(),
+    });
+
     match peer_message {
         PeerMessage::Sync(message_buf) => {
             let message = AutomergeSyncMessage::decode(&message_buf)?;
-            automerge_service.send(AutomergeCommand::ApplyMessage { message });
-            CONNECTION_EVENTS
-                .write()
-                .push(ConnectionEvent::IncomingPeerMessage {
-                    date_time: Local::now(),
-                    remote_node_id,
-                    message_type: "sync".to_string(),
-                });
+            update_connected_peer(remote_node_id, |peer| {
+                peer.messages_received += 1;
+                peer.last_sync_heads = message.heads.clone();
+            });
+            automerge_service.send(AutomergeCommand::ApplyMessage {
+                remote_node_id,
+                role,
+                message,
+                size: message_buf.len(),
+            });
         }
-        PeerMessage::Ephemeral(_message_buf) => {
-            // TODO: implement the ephemerality
+        PeerMessage::Ephemeral(message) => {
+            presence_service.send(PresenceCommand::ApplyEphemeralMessage {
+                remote_node_id,
+                message,
+            });
         }
     }
     Ok(())
 }
 
-fn start_receiving_messages(
+/// Decodes a received frame, or explains why the peer's message format doesn't match ours.
+fn decode_peer_message(
     remote_node_id: NodeId,
+    protocol_version: ProtocolVersion,
+    message_buf: &[u8],
+) -> Result<PeerMessage, ConnectionEvent> {
+    // The peer changed its message format without negotiating another version.
+    from_bytes::<PeerMessage>(message_buf).map_err(|error| {
+        ConnectionEvent::IncompatiblePeerVersion {
+            remote_node_id: Some(remote_node_id),
+            peer_version: Some(protocol_version),
+            reason: format!("failed to decode its message: {error}"),
+        }
+    })
+}
+
+fn start_receiving_messages(
+    connection: &Connection,
+    role: PeerRole,
+    protocol_version: ProtocolVersion,
     mut receive: RecvStream,
     automerge_service: Coroutine<AutomergeCommand>,
-) {
+    connection_service: Coroutine<ConnectionCommand>,
+    presence_service: Coroutine<PresenceCommand>,
+) -> Result<()> {
+    let remote_node_id = connection.remote_node_id()?;
+    let connection_id = connection.stable_id();
     spawn(async move {
-        loop {
-            let maybe_message = receive_peer_message(&mut receive).await;
-            if let Ok(peer_message) = maybe_message {
-                if let Err(error) =
-                    handle_peer_message(remote_node_id, peer_message, automerge_service)
-                {
-                    handle_error(error);
-                    // TODO: can we recover from a failed message?
-                    break;
-                }
+        // Reading fails once the stream is closed, e.g. after disconnecting.
+        while let Ok(message_buf) = receive_frame(remote_node_id, role, &mut receive).await {
+            let peer_message =
+                match decode_peer_message(remote_node_id, protocol_version, &message_buf) {
+                    Ok(peer_message) => peer_message,
+                    Err(event) => {
+                        log_event(event);
+                        break;
+                    }
+                };
+
+            if let Err(error) = handle_peer_message(
+                remote_node_id,
+                role,
+                peer_message,
+                automerge_service,
+                presence_service,
+            ) {
+                handle_error(error);
+                // TODO: can we recover from a failed message?
+                break;
             }
         }
 
-        CONNECTED_PEERS.write().retain(|&n| n != remote_node_id);
-        CONNECTION_EVENTS
-            .write()
-            .push(ConnectionEvent::Disconnected {
-                date_time: Local::now(),
-                remote_node_id,
-            });
+        connection_service.send(ConnectionCommand::ConnectionClosed {
+            remote_node_id,
+            connection_id,
+        });
     });
+    Ok(())
 }
 
 pub async fn send_message(
     remote_node_id: NodeId,
+    role: PeerRole,
     send: &mut SendStream,
-    message: AutomergeSyncMessage,
+    peer_message: PeerMessage,
 ) -> Result<()> {
-    let peer_message = PeerMessage::Sync(message.encode());
     let message_buf = to_allocvec(&peer_message)?;
     let message_len = u32::try_from(message_buf.len())?;
     send.write_all(&message_len.to_be_bytes()).await?;
 
     send.write_all(&message_buf).await?;
+    record_frame(remote_node_id, role, FrameDirection::Outgoing, &message_buf);
+    if let PeerMessage::Sync(sync_message_buf) = &peer_message {
+        record_sent_message(sync_message_buf.len());
+    }
 
-    CONNECTION_EVENTS
-        .write()
-        .push(ConnectionEvent::OutgoingPeerMessage {
-            date_time: Local::now(),
-            remote_node_id,
-            message_type: "sync".to_string(),
-        });
+    update_connected_peer(remote_node_id, |peer| peer.messages_sent += 1);
+    log_event(ConnectionEvent::OutgoingPeerMessage {
+        remote_node_id,
+        message_type: peer_message.message_type().to_string(),
+    });
 
     Ok(())
 }
 
 fn start_sending_messages(
     remote_node_id: NodeId,
+    role: PeerRole,
     mut send: SendStream,
-    mut outgoing_message_rx: Receiver<AutomergeSyncMessage>,
-) {
+) -> UnboundedSender<PeerMessage> {
+    let (outgoing_message_tx, mut outgoing_message_rx) = unbounded();
     spawn(async move {
-        while let Ok(message) = outgoing_message_rx.recv().await {
-            if let Err(error) = send_message(remote_node_id, &mut send, message).await {
+        while let Some(message) = outgoing_message_rx.next().await {
+            if let Err(error) = send_message(remote_node_id, role, &mut send, message).await {
                 handle_error(error);
+                // The stream is unusable, dropping the receiver lets the service forget the peer.
+                break;
             }
         }
     });
+    outgoing_message_tx
+}
+
+fn start_refreshing_peer_details(
+    remote_node_id: NodeId,
+    connection: Connection,
+    connection_type: Option<Watcher<ConnectionType>>,
+) {
+    spawn(async move {
+        while connection.close_reason().is_none() {
+            let stats = connection.stats();
+            update_connected_peer(remote_node_id, |peer| {
+                peer.connection_type = connection_type
+                    .as_ref()
+                    .and_then(|watcher| watcher.get().ok());
+                peer.round_trip_time = connection.rtt();
+                peer.bytes_received = stats.udp_rx.bytes;
+                peer.bytes_sent = stats.udp_tx.bytes;
+            });
+            sleep(PEER_DETAILS_REFRESH_INTERVAL).await;
+        }
+    });
 }
 
 async fn handle_connection_command(
     command: ConnectionCommand,
-    outgoing_message_tx: &Sender<AutomergeSyncMessage>,
+    peer_connections: &mut HashMap<NodeId, PeerConnection>,
     automerge_service: Coroutine<AutomergeCommand>,
+    connection_service: Coroutine<ConnectionCommand>,
+    presence_service: Coroutine<PresenceCommand>,
 ) -> Result<()> {
     match command {
+        ConnectionCommand::ConnectionClosed {
+            remote_node_id,
+            connection_id,
+        } => {
+            // The peer may have reconnected in the meantime.
+            if peer_connections
+                .get(&remote_node_id)
+                .is_some_and(|peer_connection| {
+                    peer_connection.connection.stable_id() != connection_id
+                })
+            {
+                return Ok(());
+            }
+
+            peer_connections.remove(&remote_node_id);
+            CONNECTED_PEERS
+                .write()
+                .retain(|peer| peer.node_id != remote_node_id);
+            automerge_service.send(AutomergeCommand::ForgetPeer { remote_node_id });
+            log_event(ConnectionEvent::Disconnected { remote_node_id });
+        }
+        ConnectionCommand::Disconnect { remote_node_id } => {
+            let Some(peer_connection) = peer_connections.remove(&remote_node_id) else {
+                return Err(anyhow!("not connected to {remote_node_id}!"));
+            };
+            peer_connection
+                .connection
+                .close(0u32.into(), b"disconnected by user");
+        }
         ConnectionCommand::NewConnection {
             connection,
+            connection_type,
             receive,
             send,
+            role,
+            protocol_version,
         } => {
             let remote_node_id = connection.remote_node_id()?;
-            start_receiving_messages(remote_node_id, receive, automerge_service);
-            start_sending_messages(remote_node_id, send, outgoing_message_tx.subscribe());
+            start_receiving_messages(
+                &connection,
+                role,
+                protocol_version,
+                receive,
+                automerge_service,
+                connection_service,
+                presence_service,
+            )?;
+            let outgoing_message_tx = start_sending_messages(remote_node_id, role, send);
+            start_refreshing_peer_details(remote_node_id, connection.clone(), connection_type);
 
-            CONNECTED_PEERS.write().push(remote_node_id);
-            CONNECTION_EVENTS.write().push(ConnectionEvent::Connected {
-                date_time: Local::now(),
+            let connected_at = Local::now();
+            let mut connected_peers = CONNECTED_PEERS.write();
+            // A reconnecting peer replaces its previous connection.
+            connected_peers.retain(|peer| peer.node_id != remote_node_id);
+            connected_peers.push(ConnectedPeer {
+                node_id: remote_node_id,
+                role,
+                protocol_version,
+                connected_at,
+                connection_type: None,
+                round_trip_time: connection.rtt(),
+                bytes_received: 0,
+                bytes_sent: 0,
+                messages_received: 0,
+                messages_sent: 0,
+                last_sync_heads: Vec::new(),
+            });
+            drop(connected_peers);
+            log_event(ConnectionEvent::Connected {
                 remote_node_id,
+                role,
             });
 
-            automerge_service.send(AutomergeCommand::StartSync);
+            let previous_peer_connection = peer_connections.insert(
+                remote_node_id,
+                PeerConnection {
+                    connection,
+                    outgoing_message_tx,
+                },
+            );
+            if let Some(previous_peer_connection) = previous_peer_connection {
+                previous_peer_connection
+                    .connection
+                    .close(0u32.into(), b"replaced by a new connection");
+            }
+
+            automerge_service.send(AutomergeCommand::ResetSync { remote_node_id });
+            automerge_service.send(AutomergeCommand::StartSync { remote_node_id });
+            presence_service.send(PresenceCommand::AnnounceTo { remote_node_id });
+        }
+        ConnectionCommand::SendEphemeralMessage {
+            remote_node_id,
+            message,
+        } => {
+            queue_peer_message(
+                peer_connections,
+                remote_node_id,
+                PeerMessage::Ephemeral(message),
+            )?;
         }
-        ConnectionCommand::SendMessage { message } => {
-            outgoing_message_tx.send(message)?;
+        ConnectionCommand::SendMessage {
+            remote_node_id,
+            message,
+        } => {
+            queue_peer_message(
+                peer_connections,
+                remote_node_id,
+                PeerMessage::Sync(message.encode()),
+            )?;
         }
     }
     Ok(())
 }
 
+fn queue_peer_message(
+    peer_connections: &mut HashMap<NodeId, PeerConnection>,
+    remote_node_id: NodeId,
+    peer_message: PeerMessage,
+) -> Result<()> {
+    let Some(peer_connection) = peer_connections.get(&remote_node_id) else {
+        return Err(anyhow!("not connected to {remote_node_id}!"));
+    };
+    if peer_connection
+        .outgoing_message_tx
+        .unbounded_send(peer_message)
+        .is_err()
+    {
+        peer_connections.remove(&remote_node_id);
+        return Err(anyhow!("connection to {remote_node_id} is closed!"));
+    }
+    Ok(())
+}
+
 pub async fn start_connection_service(mut commands_rx: UnboundedReceiver<ConnectionCommand>) {
     let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let connection_service = use_coroutine_handle::<ConnectionCommand>();
+    let presence_service = use_coroutine_handle::<PresenceCommand>();
 
-    let (outgoing_message_tx, _) = broadcast::channel(16);
+    let mut peer_connections = HashMap::new();
 
     while let Some(command) = commands_rx.next().await {
-        if let Err(error) =
-            handle_connection_command(command, &outgoing_message_tx, automerge_service).await
+        if let Err(error) = handle_connection_command(
+            command,
+            &mut peer_connections,
+            automerge_service,
+            connection_service,
+            presence_service,
+        )
+        .await
         {
             handle_error(error);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::endpoint::ConnectOptions;
+    use iroh::{Endpoint, RelayMode, SecretKey};
+
+    const NEWER_ALPN: &[u8] = b"/ethersync/1";
+
+    async fn local_endpoint(alpns: Vec<Vec<u8>>) -> Endpoint {
+        Endpoint::builder()
+            .alpns(alpns)
+            .relay_mode(RelayMode::Disabled)
+            .bind()
+            .await
+            .unwrap()
+    }
+
+    /// Connects with the ALPNs in our order of preference.
+    async fn connect(
+        endpoint: &Endpoint,
+        remote: &Endpoint,
+        mut alpns: Vec<Vec<u8>>,
+    ) -> Result<Connection, ConnectionError> {
+        let preferred_alpn = alpns.remove(0);
+        let connecting = endpoint
+            .connect_with_opts(
+                remote.node_addr().await.unwrap(),
+                &preferred_alpn,
+                ConnectOptions::new().with_additional_alpns(alpns),
+            )
+            .await
+            .unwrap();
+        connecting.await
+    }
+
+    #[test]
+    fn only_supported_versions_are_negotiated() {
+        assert_eq!(
+            ProtocolVersion::from_alpn(b"/ethersync/0").unwrap(),
+            ProtocolVersion::Ethersync0
+        );
+        let error = ProtocolVersion::from_alpn(NEWER_ALPN).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "peer negotiated unknown protocol '/ethersync/1'!"
+        );
+    }
+
+    #[test]
+    fn undecodable_message_is_an_incompatible_peer_version() {
+        let remote_node_id = SecretKey::generate(rand::thread_rng()).public();
+        let message_buf = to_allocvec(&PeerMessage::Sync(vec![1, 2, 3])).unwrap();
+        assert!(matches!(
+            decode_peer_message(remote_node_id, ProtocolVersion::Ethersync0, &message_buf),
+            Ok(PeerMessage::Sync(sync_message_buf)) if sync_message_buf == [1, 2, 3]
+        ));
+
+        // A message type we don't know, as sent by a newer peer.
+        let Err(event) = decode_peer_message(remote_node_id, ProtocolVersion::Ethersync0, &[9])
+        else {
+            panic!("an unknown message type was decoded");
+        };
+        assert_eq!(event.level(), LogLevel::Error);
+        let ConnectionEvent::IncompatiblePeerVersion {
+            remote_node_id: Some(node_id),
+            peer_version: Some(ProtocolVersion::Ethersync0),
+            reason,
+        } = event
+        else {
+            panic!("the event doesn't name the peer and its version");
+        };
+        assert_eq!(node_id, remote_node_id);
+        assert!(reason.starts_with("failed to decode its message"));
+    }
+
+    #[tokio::test]
+    async fn accepting_side_with_our_versions_negotiates_ours() {
+        let accepting = local_endpoint(ProtocolVersion::supported_alpns()).await;
+        let dialing = local_endpoint(Vec::new()).await;
+
+        let accept = tokio::spawn({
+            let accepting = accepting.clone();
+            async move { accepting.accept().await.unwrap().await.unwrap() }
+        });
+        let connection = connect(&dialing, &accepting, ProtocolVersion::supported_alpns())
+            .await
+            .unwrap();
+        accept.await.unwrap();
+
+        assert_eq!(
+            ProtocolVersion::negotiated(&connection).unwrap(),
+            ProtocolVersion::SUPPORTED[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn no_common_version_is_a_protocol_mismatch() {
+        let accepting = local_endpoint(vec![NEWER_ALPN.to_vec()]).await;
+        let dialing = local_endpoint(Vec::new()).await;
+
+        let accept = tokio::spawn({
+            let accepting = accepting.clone();
+            async move { accepting.accept().await.unwrap().await }
+        });
+        let error = connect(&dialing, &accepting, ProtocolVersion::supported_alpns())
+            .await
+            .unwrap_err();
+
+        assert!(is_protocol_mismatch(&error), "{error}");
+        assert!(is_protocol_mismatch(&accept.await.unwrap().unwrap_err()));
+    }
+}
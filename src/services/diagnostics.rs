@@ -0,0 +1,97 @@
+use chrono::TimeDelta;
+use dioxus::prelude::{GlobalSignal, Signal};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{sync::LazyLock, time::Instant};
+
+/// Size of the document, which is expensive to compute and only refreshed on request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DocumentStatistics {
+    pub saved_bytes: usize,
+    pub change_count: usize,
+    pub actor_count: usize,
+    pub file_count: usize,
+    /// Characters in all text files.
+    pub text_length: usize,
+}
+
+/// Sync messages sent or received in one direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MessageStatistics {
+    pub message_count: usize,
+    pub total_bytes: usize,
+    pub largest_bytes: usize,
+}
+
+impl MessageStatistics {
+    fn record(&mut self, size: usize) {
+        self.message_count += 1;
+        self.total_bytes += size;
+        self.largest_bytes = self.largest_bytes.max(size);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimingStatistics {
+    pub count: u32,
+    pub total: TimeDelta,
+    pub longest: TimeDelta,
+}
+
+impl TimingStatistics {
+    fn record(&mut self, duration: TimeDelta) {
+        self.count += 1;
+        self.total += duration;
+        self.longest = self.longest.max(duration);
+    }
+
+    pub fn average(&self) -> TimeDelta {
+        match self.count {
+            0 => TimeDelta::zero(),
+            count => self.total / count as i32,
+        }
+    }
+}
+
+/// Numbers to decide whether a long running session should be compacted or restarted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    pub document: Option<DocumentStatistics>,
+    pub sent_messages: MessageStatistics,
+    pub received_messages: MessageStatistics,
+    /// Time spent applying received sync messages to the document.
+    pub applying_messages: TimingStatistics,
+}
+
+pub static DIAGNOSTICS: GlobalSignal<Diagnostics> = Signal::global(Diagnostics::default);
+
+/// Milliseconds on a monotonic clock, as `Instant` isn't available in browsers.
+#[cfg(target_arch = "wasm32")]
+fn now_millis() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map_or(0.0, |performance| performance.now())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_millis() -> f64 {
+    static STARTED_AT: LazyLock<Instant> = LazyLock::new(Instant::now);
+    STARTED_AT.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Runs `f` and returns its result together with how long it took.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, TimeDelta) {
+    let started_at = now_millis();
+    let result = f();
+    let duration = TimeDelta::microseconds(((now_millis() - started_at) * 1000.0) as i64);
+    (result, duration)
+}
+
+pub fn record_sent_message(size: usize) {
+    DIAGNOSTICS.write().sent_messages.record(size);
+}
+
+pub fn record_received_message(size: usize, duration: TimeDelta) {
+    let mut diagnostics = DIAGNOSTICS.write();
+    diagnostics.received_messages.record(size);
+    diagnostics.applying_messages.record(duration);
+}
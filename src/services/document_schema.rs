@@ -0,0 +1,286 @@
+use anyhow::Result;
+use automerge::{ChangeHash, ObjId, ObjType, ReadDoc, ScalarValue, Value};
+use derive_more::Display;
+use dioxus::prelude::{GlobalSignal, Signal};
+use serde_json::{Map as JsonMap, Number, Value as JsonValue};
+use std::collections::BTreeMap;
+
+pub const FILES_KEY: &str = "files";
+pub const STATES_KEY: &str = "states";
+/// Optional marker of the document format, which the daemon may add in later versions.
+pub const VERSION_KEY: &str = "version";
+/// Newest document format this client understands.
+pub const SUPPORTED_VERSION: i64 = 1;
+
+/// Difference between a document and the ethersync document format.
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum SchemaMismatch {
+    #[display("the document has no '{key}' object")]
+    MissingObject { key: &'static str },
+    #[display("'{key}' is a {found} instead of a {expected}")]
+    UnexpectedType {
+        key: &'static str,
+        expected: ObjType,
+        found: String,
+    },
+    #[display("file '{file_name}' is a {found} instead of a text or bytes")]
+    UnsupportedFile { file_name: String, found: String },
+    #[display(
+        "document version {found} is not supported, only up to {}",
+        SUPPORTED_VERSION
+    )]
+    UnsupportedVersion { found: String },
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+/// Schema mismatches of the current document, which are shown until a peer fixes them.
+pub static SCHEMA_MISMATCHES: GlobalSignal<Vec<SchemaMismatch>> = Signal::global(Vec::new);
+
+/// Entries of the `states` map, which the daemon uses to share state besides the files.
+pub static DOCUMENT_STATES: GlobalSignal<BTreeMap<String, JsonValue>> =
+    Signal::global(BTreeMap::new);
+
+fn value_kind(value: &Value) -> String {
+    match value {
+        Value::Object(obj_type) => obj_type.to_string(),
+        Value::Scalar(scalar) => match scalar.as_ref() {
+            ScalarValue::Bytes(_) => "bytes".to_owned(),
+            ScalarValue::Str(_) => "string".to_owned(),
+            ScalarValue::Boolean(_) => "boolean".to_owned(),
+            ScalarValue::Null => "null".to_owned(),
+            _ => "number".to_owned(),
+        },
+    }
+}
+
+fn is_binary(value: &Value) -> bool {
+    matches!(value, Value::Scalar(scalar) if matches!(scalar.as_ref(), ScalarValue::Bytes(_)))
+}
+
+fn get<'a>(
+    doc: &'a impl ReadDoc,
+    object_id: &ObjId,
+    key: &str,
+    heads: Option<&[ChangeHash]>,
+) -> Result<Option<(Value<'a>, ObjId)>> {
+    Ok(match heads {
+        Some(heads) => doc.get_at(object_id, key, heads)?,
+        None => doc.get(object_id, key)?,
+    })
+}
+
+/// Object ids of the parts of an ethersync document.
+///
+/// Keys which are not part of the format are ignored, so documents of newer daemons with
+/// additional fields keep working.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentSchema {
+    pub files: ObjId,
+    pub states: Option<ObjId>,
+    pub version: Option<i64>,
+}
+
+impl DocumentSchema {
+    /// Reads the schema at `heads`, or of the current document without them.
+    ///
+    /// Only fails with a [`SchemaMismatch`] if there are no files to work with, see
+    /// [`DocumentSchema::mismatches`] for everything else.
+    pub fn read(doc: &impl ReadDoc, heads: Option<&[ChangeHash]>) -> Result<Self> {
+        let files = match get(doc, &automerge::ROOT, FILES_KEY, heads)? {
+            Some((Value::Object(ObjType::Map), files)) => files,
+            Some((value, _)) => {
+                return Err(SchemaMismatch::UnexpectedType {
+                    key: FILES_KEY,
+                    expected: ObjType::Map,
+                    found: value_kind(&value),
+                }
+                .into())
+            }
+            None => return Err(SchemaMismatch::MissingObject { key: FILES_KEY }.into()),
+        };
+
+        let states = match get(doc, &automerge::ROOT, STATES_KEY, heads)? {
+            Some((Value::Object(ObjType::Map), states)) => Some(states),
+            _ => None,
+        };
+        let version =
+            get(doc, &automerge::ROOT, VERSION_KEY, heads)?.and_then(|(value, _)| value.to_i64());
+
+        Ok(Self {
+            files,
+            states,
+            version,
+        })
+    }
+
+    /// Returns everything in the current document which doesn't match the format.
+    pub fn mismatches(doc: &impl ReadDoc) -> Result<Vec<SchemaMismatch>> {
+        let schema = match Self::read(doc, None) {
+            Ok(schema) => schema,
+            Err(error) => match error.downcast::<SchemaMismatch>() {
+                Ok(mismatch) => return Ok(vec![mismatch]),
+                Err(error) => return Err(error),
+            },
+        };
+
+        let mut mismatches = Vec::new();
+        match get(doc, &automerge::ROOT, STATES_KEY, None)? {
+            Some((Value::Object(ObjType::Map), _)) => {}
+            Some((value, _)) => mismatches.push(SchemaMismatch::UnexpectedType {
+                key: STATES_KEY,
+                expected: ObjType::Map,
+                found: value_kind(&value),
+            }),
+            None => mismatches.push(SchemaMismatch::MissingObject { key: STATES_KEY }),
+        }
+
+        if let Some((value, _)) = get(doc, &automerge::ROOT, VERSION_KEY, None)? {
+            if value
+                .to_i64()
+                .is_none_or(|version| version > SUPPORTED_VERSION)
+            {
+                let found = match value {
+                    Value::Scalar(scalar) => scalar.to_string(),
+                    value => value_kind(&value),
+                };
+                mismatches.push(SchemaMismatch::UnsupportedVersion { found });
+            }
+        }
+
+        for file_name in doc.keys(&schema.files) {
+            if let Some((value, _)) = get(doc, &schema.files, &file_name, None)? {
+                if !matches!(value, Value::Object(ObjType::Text)) && !is_binary(&value) {
+                    mismatches.push(SchemaMismatch::UnsupportedFile {
+                        file_name,
+                        found: value_kind(&value),
+                    });
+                }
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Names of the files at `heads`, or in the current document without them, skipping entries
+    /// which are not texts.
+    pub fn file_names(&self, doc: &impl ReadDoc, heads: Option<&[ChangeHash]>) -> Vec<String> {
+        let keys: Vec<String> = match heads {
+            Some(heads) => doc.keys_at(&self.files, heads).collect(),
+            None => doc.keys(&self.files).collect(),
+        };
+        keys.into_iter()
+            .filter(|file_name| self.file(doc, file_name, heads).is_ok())
+            .collect()
+    }
+
+    /// Names of the binary files in the current document.
+    pub fn binary_file_names(&self, doc: &impl ReadDoc) -> Vec<String> {
+        doc.keys(&self.files)
+            .filter(|file_name| {
+                get(doc, &self.files, file_name, None)
+                    .is_ok_and(|entry| entry.is_some_and(|(value, _)| is_binary(&value)))
+            })
+            .collect()
+    }
+
+    /// Returns the text object of a file.
+    pub fn file(
+        &self,
+        doc: &impl ReadDoc,
+        file_name: &str,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<ObjId> {
+        match get(doc, &self.files, file_name, heads)? {
+            Some((Value::Object(ObjType::Text), object_id)) => Ok(object_id),
+            Some((value, _)) if is_binary(&value) => {
+                anyhow::bail!("file '{file_name}' is binary, not a text!")
+            }
+            Some((value, _)) => Err(SchemaMismatch::UnsupportedFile {
+                file_name: file_name.to_owned(),
+                found: value_kind(&value),
+            }
+            .into()),
+            None => anyhow::bail!("no file '{file_name}' found!"),
+        }
+    }
+
+    /// Returns the content of a binary file in the current document.
+    pub fn binary_file(&self, doc: &impl ReadDoc, file_name: &str) -> Result<Vec<u8>> {
+        self.read_binary_file(doc, file_name, <[u8]>::to_vec)
+    }
+
+    /// Returns the size of a binary file in the current document, without copying its content.
+    pub fn binary_file_size(&self, doc: &impl ReadDoc, file_name: &str) -> Result<usize> {
+        self.read_binary_file(doc, file_name, <[u8]>::len)
+    }
+
+    fn read_binary_file<T>(
+        &self,
+        doc: &impl ReadDoc,
+        file_name: &str,
+        read: impl FnOnce(&[u8]) -> T,
+    ) -> Result<T> {
+        let Some((value, _)) = get(doc, &self.files, file_name, None)? else {
+            anyhow::bail!("no file '{file_name}' found!")
+        };
+        match value {
+            Value::Scalar(scalar) => match scalar.as_ref() {
+                ScalarValue::Bytes(bytes) => Ok(read(bytes)),
+                _ => anyhow::bail!("file '{file_name}' is not binary!"),
+            },
+            Value::Object(_) => anyhow::bail!("file '{file_name}' is not binary!"),
+        }
+    }
+
+    /// Reads the entries of the `states` map as JSON, as their structure is up to the daemon.
+    pub fn states(&self, doc: &impl ReadDoc) -> Result<BTreeMap<String, JsonValue>> {
+        let mut states = BTreeMap::new();
+        let Some(states_object) = &self.states else {
+            return Ok(states);
+        };
+
+        for key in doc.keys(states_object) {
+            if let Some((value, object_id)) = get(doc, states_object, &key, None)? {
+                let value = json_value(doc, value, &object_id)?;
+                states.insert(key, value);
+            }
+        }
+        Ok(states)
+    }
+}
+
+fn json_value(doc: &impl ReadDoc, value: Value, object_id: &ObjId) -> Result<JsonValue> {
+    Ok(match value {
+        Value::Scalar(scalar) => match scalar.as_ref() {
+            ScalarValue::Str(text) => JsonValue::String(text.to_string()),
+            ScalarValue::Int(number) | ScalarValue::Timestamp(number) => (*number).into(),
+            ScalarValue::Uint(number) => (*number).into(),
+            ScalarValue::F64(number) => {
+                Number::from_f64(*number).map_or(JsonValue::Null, JsonValue::Number)
+            }
+            ScalarValue::Counter(counter) => i64::from(counter).into(),
+            ScalarValue::Boolean(boolean) => (*boolean).into(),
+            ScalarValue::Null => JsonValue::Null,
+            scalar => JsonValue::String(scalar.to_string()),
+        },
+        Value::Object(ObjType::Text) => JsonValue::String(doc.text(object_id)?),
+        Value::Object(ObjType::List) => {
+            let mut items = Vec::new();
+            for index in 0..doc.length(object_id) {
+                if let Some((value, item_id)) = doc.get(object_id, index)? {
+                    items.push(json_value(doc, value, &item_id)?);
+                }
+            }
+            JsonValue::Array(items)
+        }
+        Value::Object(ObjType::Map | ObjType::Table) => {
+            let mut entries = JsonMap::new();
+            for key in doc.keys(object_id) {
+                if let Some((value, entry_id)) = get(doc, object_id, &key, None)? {
+                    entries.insert(key, json_value(doc, value, &entry_id)?);
+                }
+            }
+            JsonValue::Object(entries)
+        }
+    })
+}
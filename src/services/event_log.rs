@@ -0,0 +1,104 @@
+use chrono::{DateTime, Local};
+use derive_more::Display;
+use dioxus::prelude::{GlobalSignal, Signal};
+use iroh::NodeId;
+use std::collections::VecDeque;
+
+/// Number of entries kept in [`EVENT_LOG`] before the oldest ones are dropped.
+const EVENT_LOG_CAPACITY: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    #[display("debug")]
+    Debug,
+    #[display("info")]
+    Info,
+    #[display("warning")]
+    Warning,
+    #[display("error")]
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warning,
+        LogLevel::Error,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum LogSource {
+    #[display("automerge")]
+    Automerge,
+    #[display("connection")]
+    Connection,
+    #[display("node")]
+    Node,
+    #[display("presence")]
+    Presence,
+}
+
+impl LogSource {
+    pub const ALL: [LogSource; 4] = [
+        LogSource::Automerge,
+        LogSource::Connection,
+        LogSource::Node,
+        LogSource::Presence,
+    ];
+}
+
+/// Events of the services which end up in the [`EVENT_LOG`].
+pub trait LogEvent: Display {
+    fn level(&self) -> LogLevel;
+
+    fn source(&self) -> LogSource;
+
+    /// Peer the event is about, if any.
+    fn peer(&self) -> Option<NodeId> {
+        None
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct LogEntry {
+    pub date_time: DateTime<Local>,
+    pub level: LogLevel,
+    pub source: LogSource,
+    pub peer: Option<NodeId>,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Serializes the entry as a single line of JSON, see <https://jsonlines.org/>.
+    pub fn to_json_line(&self) -> String {
+        serde_json::json!({
+            "date_time": self.date_time.to_rfc3339(),
+            "level": self.level.to_string(),
+            "source": self.source.to_string(),
+            "peer": self.peer.map(|peer| peer.to_string()),
+            "message": self.message,
+        })
+        .to_string()
+    }
+}
+
+pub static EVENT_LOG: GlobalSignal<VecDeque<LogEntry>> =
+    Signal::global(|| VecDeque::with_capacity(EVENT_LOG_CAPACITY));
+
+pub fn log_event(event: impl LogEvent) {
+    let entry = LogEntry {
+        date_time: Local::now(),
+        level: event.level(),
+        source: event.source(),
+        peer: event.peer(),
+        message: event.to_string(),
+    };
+
+    let mut event_log = EVENT_LOG.write();
+    if event_log.len() >= EVENT_LOG_CAPACITY {
+        event_log.pop_front();
+    }
+    event_log.push_back(entry);
+}
@@ -1,61 +1,195 @@
-use crate::services::connection_service::ConnectionCommand;
+use crate::services::connection_service::{
+    is_protocol_mismatch, log_protocol_mismatch, ConnectionCommand, PeerRole, ProtocolVersion,
+};
+use crate::services::event_log::{log_event, LogEvent, LogLevel, LogSource};
 use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::ops::Deref;
 use std::str::FromStr;
+use std::time::Duration;
 
-use anyhow::{bail, Error, Result};
+use anyhow::{anyhow, bail, Context, Error, Result};
+use async_std::future::timeout;
+use automerge::ActorId;
 use chrono::{DateTime, Local};
 use dioxus::hooks::use_coroutine_handle;
-use dioxus::prelude::{spawn, Coroutine, GlobalSignal, Signal};
+use dioxus::prelude::{document, spawn, Coroutine, GlobalSignal, Readable, Signal, Task};
 use futures::channel::mpsc::UnboundedReceiver;
-use futures::StreamExt;
-use iroh::endpoint::Incoming;
-use iroh::{Endpoint, NodeId, SecretKey};
+use futures::{Future, StreamExt};
+use iroh::endpoint::{ConnectOptions, Incoming};
+use iroh::{Endpoint, NodeAddr, NodeId, SecretKey};
 use magic_wormhole::{transfer, AppID, Code, MailboxConnection, Wormhole};
 
-const ALPN: &[u8] = b"/ethersync/0";
+/// Version of the JSON join ticket format understood by [`JoinTicket::from_str`].
+const JOIN_TICKET_VERSION: u32 = 1;
+
+const CONNECTION_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Local storage entry keeping our secret key, so the node ID survives reloads.
+const SECRET_KEY_STORAGE_KEY: &str = "ethersync-web.secret-key";
 
 #[derive(Clone, PartialEq)]
 pub struct EthersyncNodeInfo {
     pub node_id: NodeId,
+    /// Actor of our changes, unique per session so that tabs sharing the node ID never reuse
+    /// the sequence numbers of each other.
+    pub actor_id: ActorId,
     pub my_passphrase: String,
+    pub viewer_passphrase: String,
     pub secret_key: String,
 }
 
 pub static NODE_INFO: GlobalSignal<Option<EthersyncNodeInfo>> = Signal::global(|| None);
 
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum ConnectionStage {
+    #[display("connecting to mailbox")]
+    Mailbox,
+    #[display("exchanging keys")]
+    KeyExchange,
+    #[display("dialing peer")]
+    Dialing,
+    #[display("authenticating")]
+    Authenticating,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ConnectionAttempt {
+    pub started_at: DateTime<Local>,
+    pub stage: ConnectionStage,
+}
+
+pub static CONNECTION_ATTEMPT: GlobalSignal<Option<ConnectionAttempt>> = Signal::global(|| None);
+
 pub enum NodeEvent {
+    ConnectionAttemptCancelled,
+    ConnectionAttemptStage {
+        stage: ConnectionStage,
+    },
     Error {
-        date_time: DateTime<Local>,
         error: Error,
     },
-    Spawned {
-        date_time: DateTime<Local>,
+    ReceivedJoinTicket {
+        peer_node_id: NodeId,
+        project_label: Option<String>,
     },
+    SecretKeyInUse,
+    Spawned,
 }
 
 impl Display for NodeEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            NodeEvent::Error { date_time, error } => write!(f, "{date_time}: node error {error}"),
-            NodeEvent::Spawned { date_time } => write!(f, "{date_time}: node spawned"),
+            NodeEvent::ConnectionAttemptCancelled => {
+                write!(f, "connection attempt cancelled")
+            }
+            NodeEvent::ConnectionAttemptStage { stage } => {
+                write!(f, "connection attempt {stage}")
+            }
+            NodeEvent::Error { error } => write!(f, "node error {error}"),
+            NodeEvent::ReceivedJoinTicket {
+                peer_node_id,
+                project_label: Some(project_label),
+            } => write!(
+                f,
+                "received join ticket for '{project_label}' from {peer_node_id}"
+            ),
+            NodeEvent::ReceivedJoinTicket {
+                peer_node_id,
+                project_label: None,
+            } => write!(f, "received join ticket from {peer_node_id}"),
+            NodeEvent::SecretKeyInUse => write!(
+                f,
+                "another tab uses the stored secret key, this tab uses a temporary one"
+            ),
+            NodeEvent::Spawned => write!(f, "node spawned"),
         }
     }
 }
 
-pub static NODE_EVENTS: GlobalSignal<Vec<NodeEvent>> = Signal::global(Vec::new);
+impl LogEvent for NodeEvent {
+    fn level(&self) -> LogLevel {
+        match self {
+            NodeEvent::ConnectionAttemptStage { .. } => LogLevel::Debug,
+            NodeEvent::Error { .. } => LogLevel::Error,
+            NodeEvent::SecretKeyInUse => LogLevel::Warning,
+            NodeEvent::ConnectionAttemptCancelled
+            | NodeEvent::ReceivedJoinTicket { .. }
+            | NodeEvent::Spawned => LogLevel::Info,
+        }
+    }
+
+    fn source(&self) -> LogSource {
+        LogSource::Node
+    }
+
+    fn peer(&self) -> Option<NodeId> {
+        match self {
+            NodeEvent::ReceivedJoinTicket { peer_node_id, .. } => Some(*peer_node_id),
+            _ => None,
+        }
+    }
+}
 
 fn handle_error(error: Error) {
-    NODE_EVENTS.write().push(NodeEvent::Error {
-        date_time: Local::now(),
-        error,
-    });
+    log_event(NodeEvent::Error { error });
 }
 
 fn generate_random_secret_key() -> SecretKey {
     SecretKey::generate(rand::thread_rng())
 }
 
+/// Returns the secret key stored in the browser, or stores a newly generated one.
+///
+/// Only one tab at a time gets the stored key, as two endpoints with the same node ID steal
+/// connections from each other. Other tabs get a temporary key.
+async fn load_or_generate_secret_key() -> Result<SecretKey> {
+    let generated_secret_key = generate_random_secret_key();
+    let eval = document::eval(
+        r#"
+        const [storageKey, generatedSecretKey] = await dioxus.recv();
+        // Held until the tab is closed, browsers without the API can't tell tabs apart.
+        const lockAcquired = !navigator.locks || await new Promise((resolve) => {
+            navigator.locks.request(storageKey, { ifAvailable: true }, (lock) => {
+                resolve(lock !== null);
+                return lock && new Promise(() => {});
+            });
+        });
+        if (!lockAcquired) {
+            return null;
+        }
+        const storedSecretKey = localStorage.getItem(storageKey);
+        if (storedSecretKey) {
+            return storedSecretKey;
+        }
+        localStorage.setItem(storageKey, generatedSecretKey);
+        return generatedSecretKey;
+        "#,
+    );
+    eval.send((SECRET_KEY_STORAGE_KEY, generated_secret_key.to_string()))
+        .map_err(|error| anyhow!("Failed to load secret key: {error}"))?;
+    let secret_key: Option<String> = eval
+        .join()
+        .await
+        .map_err(|error| anyhow!("Failed to load secret key: {error}"))?;
+    match secret_key {
+        Some(secret_key) => {
+            SecretKey::from_str(&secret_key).context("Failed to parse stored secret key")
+        }
+        None => {
+            log_event(NodeEvent::SecretKeyInUse);
+            Ok(generated_secret_key)
+        }
+    }
+}
+
+fn session_actor_id(node_id: NodeId) -> ActorId {
+    let mut bytes = node_id.as_bytes().to_vec();
+    bytes.extend(rand::random::<[u8; 8]>());
+    ActorId::from(bytes)
+}
+
 pub struct SecretAddress {
     pub peer_node_id: NodeId,
     pub peer_passphrase: SecretKey,
@@ -81,7 +215,100 @@ impl SecretAddress {
     }
 }
 
+/// Payload exchanged via magic wormhole to join a peer.
+///
+/// Older daemons send the plain `node_id#passphrase` string, newer ones a versioned JSON object
+/// which may carry additional hints.
+pub struct JoinTicket {
+    pub secret_address: SecretAddress,
+    pub direct_addresses: Vec<SocketAddr>,
+    pub project_label: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct JoinTicketPayload {
+    version: u32,
+    node_id: String,
+    passphrase: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    direct_addresses: Vec<SocketAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_label: Option<String>,
+}
+
+impl JoinTicket {
+    fn from_legacy_string(value: &str) -> Result<Self> {
+        let Some((peer_node_id, peer_passphrase)) = value.split_once('#') else {
+            bail!("join ticket is missing the '#' separator!")
+        };
+
+        if peer_passphrase.contains('#') {
+            bail!("join ticket contains too many '#' separators!")
+        }
+
+        Ok(Self {
+            secret_address: SecretAddress::from_string(
+                peer_node_id.to_string(),
+                peer_passphrase.to_string(),
+            )?,
+            direct_addresses: Vec::new(),
+            project_label: None,
+        })
+    }
+
+    fn from_json_string(value: &str) -> Result<Self> {
+        let payload: JoinTicketPayload =
+            serde_json::from_str(value).context("Failed to parse join ticket")?;
+
+        if payload.version > JOIN_TICKET_VERSION {
+            bail!(
+                "join ticket version {} is not supported (expected at most {JOIN_TICKET_VERSION})!",
+                payload.version
+            )
+        }
+
+        Ok(Self {
+            secret_address: SecretAddress::from_string(payload.node_id, payload.passphrase)?,
+            direct_addresses: payload.direct_addresses,
+            project_label: payload.project_label.filter(|label| !label.is_empty()),
+        })
+    }
+
+    pub fn node_addr(&self) -> NodeAddr {
+        NodeAddr::new(self.secret_address.peer_node_id)
+            .with_direct_addresses(self.direct_addresses.iter().copied())
+    }
+}
+
+impl FromStr for JoinTicket {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.starts_with('{') {
+            Self::from_json_string(value)
+        } else {
+            Self::from_legacy_string(value)
+        }
+    }
+}
+
+impl Display for JoinTicket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let payload = JoinTicketPayload {
+            version: JOIN_TICKET_VERSION,
+            node_id: self.secret_address.peer_node_id.to_string(),
+            passphrase: self.secret_address.peer_passphrase.to_string(),
+            direct_addresses: self.direct_addresses.clone(),
+            project_label: self.project_label.clone(),
+        };
+        let json = serde_json::to_string(&payload).map_err(|_| std::fmt::Error)?;
+        write!(f, "{json}")
+    }
+}
+
 pub enum NodeCommand {
+    CancelConnectionAttempt,
     ConnectByAddress { secret_address: Box<SecretAddress> },
     ConnectByJoinCode { join_code: String },
 }
@@ -89,32 +316,54 @@ pub enum NodeCommand {
 async fn create_endpoint(secret_key: SecretKey) -> Result<Endpoint> {
     Endpoint::builder()
         .secret_key(secret_key)
-        .alpns(vec![ALPN.to_vec()])
+        .alpns(ProtocolVersion::supported_alpns())
         .discovery_n0()
         .bind()
         .await
 }
 
 async fn handle_incoming_connection(
+    endpoint: &Endpoint,
     my_passphrase: SecretKey,
+    viewer_passphrase: SecretKey,
     incoming: Incoming,
     connection_service: Coroutine<ConnectionCommand>,
 ) -> Result<()> {
-    let connection = incoming.await?;
+    let connection = match incoming.await {
+        Ok(connection) => connection,
+        Err(error) if is_protocol_mismatch(&error) => {
+            // The peer can't be identified before the handshake is done.
+            log_protocol_mismatch(None);
+            return Ok(());
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let protocol_version = ProtocolVersion::negotiated(&connection)?;
     let (send, mut receive) = connection.accept_bi().await?;
 
     let mut received_passphrase = [0; 32];
     receive.read_exact(&mut received_passphrase).await?;
 
     // Guard against timing attacks.
-    if !constant_time_eq::constant_time_eq(&received_passphrase, &my_passphrase.to_bytes()) {
-        bail!("Peer provided incorrect passphrase.");
-    }
+    let role =
+        if constant_time_eq::constant_time_eq(&received_passphrase, &my_passphrase.to_bytes()) {
+            PeerRole::Editor
+        } else if constant_time_eq::constant_time_eq(
+            &received_passphrase,
+            &viewer_passphrase.to_bytes(),
+        ) {
+            PeerRole::Viewer
+        } else {
+            bail!("Peer provided incorrect passphrase.");
+        };
 
     connection_service.send(ConnectionCommand::NewConnection {
+        connection_type: endpoint.conn_type(connection.remote_node_id()?).ok(),
         connection,
         receive,
         send,
+        role,
+        protocol_version,
     });
 
     Ok(())
@@ -123,6 +372,7 @@ async fn handle_incoming_connection(
 async fn accept_incoming_connections(
     endpoint: Endpoint,
     my_passphrase: SecretKey,
+    viewer_passphrase: SecretKey,
     connection_service: Coroutine<ConnectionCommand>,
 ) {
     spawn(async move {
@@ -131,7 +381,9 @@ async fn accept_incoming_connections(
                 None => break,
                 Some(incoming) => {
                     if let Err(error) = handle_incoming_connection(
+                        &endpoint,
                         my_passphrase.clone(),
+                        viewer_passphrase.clone(),
                         incoming,
                         connection_service,
                     )
@@ -145,58 +397,165 @@ async fn accept_incoming_connections(
     });
 }
 
+fn enter_connection_stage(stage: ConnectionStage) {
+    if let Some(attempt) = CONNECTION_ATTEMPT.write().as_mut() {
+        attempt.stage = stage;
+    }
+    log_event(NodeEvent::ConnectionAttemptStage { stage });
+}
+
 pub async fn connect(
     endpoint: Endpoint,
+    node_addr: NodeAddr,
     secret_address: &SecretAddress,
     connection_service: Coroutine<ConnectionCommand>,
 ) -> Result<()> {
-    let connection = endpoint.connect(secret_address.peer_node_id, ALPN).await?;
+    enter_connection_stage(ConnectionStage::Dialing);
+    // The accepting side picks the version, we offer all of them.
+    let mut alpns = ProtocolVersion::supported_alpns();
+    let preferred_alpn = alpns.remove(0);
+    let connecting = endpoint
+        .connect_with_opts(
+            node_addr,
+            &preferred_alpn,
+            ConnectOptions::new().with_additional_alpns(alpns),
+        )
+        .await?;
+    let connection = match connecting.await {
+        Ok(connection) => connection,
+        Err(error) if is_protocol_mismatch(&error) => {
+            log_protocol_mismatch(Some(secret_address.peer_node_id));
+            return Ok(());
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let protocol_version = ProtocolVersion::negotiated(&connection)?;
 
+    enter_connection_stage(ConnectionStage::Authenticating);
     let (mut send, receive) = connection.open_bi().await?;
 
     send.write_all(&secret_address.peer_passphrase.to_bytes())
         .await?;
 
+    // The peer we dialed is trusted to edit, as its passphrase grants us access.
     connection_service.send(ConnectionCommand::NewConnection {
+        connection_type: endpoint.conn_type(secret_address.peer_node_id).ok(),
         connection,
         receive,
         send,
+        role: PeerRole::Editor,
+        protocol_version,
     });
 
     Ok(())
 }
 
-pub async fn get_secret_address_from_wormhole(code: &str) -> Result<SecretAddress> {
+pub async fn get_join_ticket_from_wormhole(code: &str) -> Result<JoinTicket> {
     let config = transfer::APP_CONFIG.id(AppID::new("ethersync"));
 
+    enter_connection_stage(ConnectionStage::Mailbox);
     let mailbox_connection =
         MailboxConnection::connect(config, Code::from_str(code)?, false).await?;
+
+    enter_connection_stage(ConnectionStage::KeyExchange);
     let mut wormhole = Wormhole::connect(mailbox_connection).await?;
     let bytes = wormhole.receive().await?;
-    let fragments: Vec<String> = String::from_utf8(bytes)?
-        .clone()
-        .split("#")
-        .map(|value| value.to_string())
-        .collect();
-    let peer_node_id = fragments[0].to_string();
-    let peer_passphrase = fragments[1].to_string();
-    SecretAddress::from_string(peer_node_id, peer_passphrase)
+    String::from_utf8(bytes)
+        .context("join ticket is not valid UTF-8")?
+        .parse()
+}
+
+/// Runs a connection attempt in its own task, so that the node service stays responsive and the
+/// attempt can be cancelled.
+fn start_connection_attempt(
+    initial_stage: ConnectionStage,
+    attempt: impl Future<Output = Result<()>> + 'static,
+) -> Result<Task> {
+    if CONNECTION_ATTEMPT.read().is_some() {
+        bail!("another connection attempt is still running!")
+    }
+
+    *CONNECTION_ATTEMPT.write() = Some(ConnectionAttempt {
+        started_at: Local::now(),
+        stage: initial_stage,
+    });
+
+    Ok(spawn(async move {
+        let result = timeout(CONNECTION_ATTEMPT_TIMEOUT, attempt).await;
+        let Some(finished_attempt) = CONNECTION_ATTEMPT.write().take() else {
+            return;
+        };
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => handle_error(error),
+            Err(_) => handle_error(anyhow!(
+                "connection attempt timed out while {}",
+                finished_attempt.stage
+            )),
+        }
+    }))
+}
+
+fn cancel_connection_attempt(connection_task: &mut Option<Task>) {
+    let running_task = connection_task.take();
+    // Only cancel the task while it is known to be running, finished task IDs may be reused.
+    if CONNECTION_ATTEMPT.write().take().is_none() {
+        return;
+    }
+
+    if let Some(task) = running_task {
+        task.cancel();
+    }
+    log_event(NodeEvent::ConnectionAttemptCancelled);
 }
 
 async fn handle_node_command(
     endpoint: Endpoint,
     command: NodeCommand,
     connection_service: Coroutine<ConnectionCommand>,
+    connection_task: &mut Option<Task>,
 ) -> Result<()> {
     match command {
+        NodeCommand::CancelConnectionAttempt => {
+            cancel_connection_attempt(connection_task);
+        }
         NodeCommand::ConnectByAddress { secret_address } => {
-            connect(endpoint.clone(), secret_address.deref(), connection_service).await
+            let node_addr = NodeAddr::new(secret_address.peer_node_id);
+            *connection_task = Some(start_connection_attempt(
+                ConnectionStage::Dialing,
+                async move {
+                    connect(
+                        endpoint,
+                        node_addr,
+                        secret_address.deref(),
+                        connection_service,
+                    )
+                    .await
+                },
+            )?);
         }
         NodeCommand::ConnectByJoinCode { join_code } => {
-            let secret_address = get_secret_address_from_wormhole(&join_code).await?;
-            connect(endpoint.clone(), &secret_address, connection_service).await
+            *connection_task = Some(start_connection_attempt(
+                ConnectionStage::Mailbox,
+                async move {
+                    let join_ticket = get_join_ticket_from_wormhole(&join_code).await?;
+                    log_event(NodeEvent::ReceivedJoinTicket {
+                        peer_node_id: join_ticket.secret_address.peer_node_id,
+                        project_label: join_ticket.project_label.clone(),
+                    });
+                    connect(
+                        endpoint,
+                        join_ticket.node_addr(),
+                        &join_ticket.secret_address,
+                        connection_service,
+                    )
+                    .await
+                },
+            )?);
         }
     }
+    Ok(())
 }
 
 pub async fn start_node_service(mut commands_rx: UnboundedReceiver<NodeCommand>) {
@@ -204,24 +563,43 @@ pub async fn start_node_service(mut commands_rx: UnboundedReceiver<NodeCommand>)
 
     // TODO: store passphrase and allow changing it
     let my_passphrase = generate_random_secret_key();
+    let viewer_passphrase = generate_random_secret_key();
 
-    let secret_key = generate_random_secret_key();
+    let secret_key = match load_or_generate_secret_key().await {
+        Ok(secret_key) => secret_key,
+        Err(error) => {
+            handle_error(error);
+            generate_random_secret_key()
+        }
+    };
     match create_endpoint(secret_key.clone()).await {
         Ok(endpoint) => {
             *NODE_INFO.write() = Some(EthersyncNodeInfo {
                 node_id: endpoint.node_id(),
+                actor_id: session_actor_id(endpoint.node_id()),
                 my_passphrase: my_passphrase.clone().to_string(),
+                viewer_passphrase: viewer_passphrase.to_string(),
                 secret_key: secret_key.to_string(),
             });
-            NODE_EVENTS.write().push(NodeEvent::Spawned {
-                date_time: Local::now(),
-            });
+            log_event(NodeEvent::Spawned);
 
-            accept_incoming_connections(endpoint.clone(), my_passphrase, connection_service).await;
+            accept_incoming_connections(
+                endpoint.clone(),
+                my_passphrase,
+                viewer_passphrase,
+                connection_service,
+            )
+            .await;
 
+            let mut connection_task = None;
             while let Some(command) = commands_rx.next().await {
-                if let Err(error) =
-                    handle_node_command(endpoint.clone(), command, connection_service).await
+                if let Err(error) = handle_node_command(
+                    endpoint.clone(),
+                    command,
+                    connection_service,
+                    &mut connection_task,
+                )
+                .await
                 {
                     handle_error(error)
                 }
@@ -230,3 +608,110 @@ pub async fn start_node_service(mut commands_rx: UnboundedReceiver<NodeCommand>)
         Err(error) => handle_error(error),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret_address() -> SecretAddress {
+        SecretAddress {
+            peer_node_id: generate_random_secret_key().public(),
+            peer_passphrase: generate_random_secret_key(),
+        }
+    }
+
+    #[test]
+    fn json_join_ticket_round_trips() {
+        let ticket = JoinTicket {
+            secret_address: secret_address(),
+            direct_addresses: vec!["192.0.2.1:4242".parse().unwrap()],
+            project_label: Some("notes".to_string()),
+        };
+
+        let parsed = JoinTicket::from_str(&ticket.to_string()).unwrap();
+
+        assert_eq!(
+            parsed.secret_address.peer_node_id,
+            ticket.secret_address.peer_node_id
+        );
+        assert_eq!(
+            parsed.secret_address.peer_passphrase.to_bytes(),
+            ticket.secret_address.peer_passphrase.to_bytes()
+        );
+        assert_eq!(parsed.direct_addresses, ticket.direct_addresses);
+        assert_eq!(parsed.project_label, ticket.project_label);
+    }
+
+    #[test]
+    fn legacy_join_ticket_is_parsed() {
+        let secret_address = secret_address();
+        let value = format!(
+            "  {}#{}\n",
+            secret_address.peer_node_id, secret_address.peer_passphrase
+        );
+
+        let parsed = JoinTicket::from_str(&value).unwrap();
+
+        assert_eq!(
+            parsed.secret_address.peer_node_id,
+            secret_address.peer_node_id
+        );
+        assert!(parsed.direct_addresses.is_empty());
+        assert_eq!(parsed.project_label, None);
+    }
+
+    #[test]
+    fn empty_project_label_is_dropped() {
+        let secret_address = secret_address();
+        let value = format!(
+            r#"{{"version":1,"node_id":"{}","passphrase":"{}","project_label":""}}"#,
+            secret_address.peer_node_id, secret_address.peer_passphrase
+        );
+
+        let parsed = JoinTicket::from_str(&value).unwrap();
+
+        assert_eq!(parsed.project_label, None);
+    }
+
+    #[test]
+    fn invalid_join_tickets_are_rejected() {
+        let secret_address = secret_address();
+        let node_id = secret_address.peer_node_id.to_string();
+        let passphrase = secret_address.peer_passphrase.to_string();
+
+        let invalid_tickets = [
+            String::new(),
+            node_id.clone(),
+            format!("{node_id}#"),
+            format!("#{passphrase}"),
+            format!("{node_id}#{passphrase}#{passphrase}"),
+            format!("not-a-node-id#{passphrase}"),
+            format!("{node_id}#not-a-passphrase"),
+            "{".to_string(),
+            format!(r#"{{"version":1,"node_id":"{node_id}"}}"#),
+            format!(
+                r#"{{"version":{},"node_id":"{node_id}","passphrase":"{passphrase}"}}"#,
+                JOIN_TICKET_VERSION + 1
+            ),
+            format!(
+                r#"{{"version":1,"node_id":"{node_id}","passphrase":"{passphrase}","direct_addresses":["nowhere"]}}"#
+            ),
+        ];
+
+        for invalid_ticket in invalid_tickets {
+            assert!(
+                JoinTicket::from_str(&invalid_ticket).is_err(),
+                "accepted {invalid_ticket:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn session_actor_ids_are_unique_per_session() {
+        let node_id = generate_random_secret_key().public();
+        let actor_id = session_actor_id(node_id);
+
+        assert_ne!(actor_id, session_actor_id(node_id));
+        assert!(actor_id.to_bytes().starts_with(node_id.as_bytes()));
+    }
+}
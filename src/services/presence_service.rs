@@ -0,0 +1,307 @@
+use crate::services::automerge_service::{AutomergeCommand, ACTIVE_FILE, FILES};
+use crate::services::connection_service::{
+    ConnectionCommand, CursorId, CursorState, EphemeralMessage, RelativePath, CONNECTED_PEERS,
+};
+use crate::services::event_log::{log_event, LogEvent, LogLevel, LogSource};
+use crate::services::node_service::NODE_INFO;
+use anyhow::{anyhow, Error, Result};
+use chrono::{DateTime, Local, TimeDelta};
+use dioxus::hooks::use_coroutine_handle;
+use dioxus::prelude::{document, Coroutine, GlobalSignal, Readable, Signal};
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::StreamExt;
+use iroh::NodeId;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// Local storage entry keeping our display name across reloads.
+const DISPLAY_NAME_STORAGE_KEY: &str = "ethersync-web.display-name";
+
+/// Latest cursor state a peer has shared over the ephemeral channel.
+#[derive(Clone, PartialEq)]
+pub struct PeerCursor {
+    pub cursor_id: CursorId,
+    pub sequence_number: usize,
+    pub cursor_state: CursorState,
+    pub received_at: DateTime<Local>,
+}
+
+/// Peers count as idle once they haven't shared their presence for this long.
+pub const IDLE_AFTER: TimeDelta = TimeDelta::minutes(5);
+
+pub static LOCAL_DISPLAY_NAME: GlobalSignal<Option<String>> = Signal::global(|| None);
+
+/// Cursors of all peers, a daemon shares one per connected editor.
+pub static PEER_CURSORS: GlobalSignal<HashMap<(NodeId, CursorId), PeerCursor>> =
+    Signal::global(HashMap::new);
+
+/// Peer whose open file and cursor we follow, until we navigate ourselves.
+pub static FOLLOWED_PEER: GlobalSignal<Option<NodeId>> = Signal::global(|| None);
+
+impl PeerCursor {
+    /// Name of the file the peer has open, if any.
+    pub fn file_name(&self) -> Option<String> {
+        self.cursor_state
+            .file_path
+            .to_str()
+            .filter(|file_name| !file_name.is_empty())
+            .map(ToOwned::to_owned)
+    }
+
+    pub fn is_idle(&self, now: DateTime<Local>) -> bool {
+        now - self.received_at >= IDLE_AFTER
+    }
+}
+
+/// Cursors of a peer, the most recently updated first.
+pub fn peer_cursors(node_id: NodeId) -> Vec<PeerCursor> {
+    let mut peer_cursors: Vec<PeerCursor> = PEER_CURSORS
+        .read()
+        .iter()
+        .filter(|((cursor_node_id, _), _)| *cursor_node_id == node_id)
+        .map(|(_, cursor)| cursor.clone())
+        .collect();
+    peer_cursors.sort_by_key(|cursor| std::cmp::Reverse(cursor.received_at));
+    peer_cursors
+}
+
+/// Connected peers with their cursors, ordered by name.
+pub fn connected_peer_cursors() -> Vec<(NodeId, Vec<PeerCursor>)> {
+    let mut connected_peer_cursors: Vec<(NodeId, Vec<PeerCursor>)> = CONNECTED_PEERS
+        .read()
+        .iter()
+        .map(|peer| (peer.node_id, peer_cursors(peer.node_id)))
+        .collect();
+    connected_peer_cursors.sort_by_cached_key(|(node_id, _)| peer_name(*node_id));
+    connected_peer_cursors
+}
+
+pub fn stop_following() {
+    if FOLLOWED_PEER.read().is_some() {
+        *FOLLOWED_PEER.write() = None;
+    }
+}
+
+/// Opens the file of the followed peer, if it has another one open than we do.
+fn open_followed_file(automerge_service: Coroutine<AutomergeCommand>) {
+    let Some(remote_node_id) = *FOLLOWED_PEER.read() else {
+        return;
+    };
+    let Some(file_name) = peer_cursors(remote_node_id)
+        .first()
+        .and_then(PeerCursor::file_name)
+    else {
+        return;
+    };
+
+    if ACTIVE_FILE.read().as_ref() != Some(&file_name) && FILES.read().contains(&file_name) {
+        automerge_service.send(AutomergeCommand::SelectFile { file_name });
+    }
+}
+
+/// Name to show for a peer: its display name if it shared one, otherwise its short node ID.
+pub fn peer_name(node_id: NodeId) -> String {
+    peer_cursors(node_id)
+        .into_iter()
+        .find_map(|cursor| cursor.cursor_state.name)
+        .unwrap_or_else(|| node_id.fmt_short())
+}
+
+/// CSS colour derived from the node ID, so a peer keeps its colour across sessions.
+pub fn peer_colour(node_id: NodeId) -> String {
+    let bytes = node_id.as_bytes();
+    let hue = u16::from_be_bytes([bytes[0], bytes[1]]) % 360;
+    format!("hsl({hue}, 70%, 40%)")
+}
+
+pub enum PresenceEvent {
+    Error { error: Error },
+}
+
+impl Display for PresenceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresenceEvent::Error { error } => write!(f, "presence error {error}"),
+        }
+    }
+}
+
+impl LogEvent for PresenceEvent {
+    fn level(&self) -> LogLevel {
+        match self {
+            PresenceEvent::Error { .. } => LogLevel::Error,
+        }
+    }
+
+    fn source(&self) -> LogSource {
+        LogSource::Presence
+    }
+}
+
+async fn load_display_name() -> Result<Option<String>> {
+    let eval = document::eval(
+        r#"
+        const storageKey = await dioxus.recv();
+        return localStorage.getItem(storageKey);
+        "#,
+    );
+    eval.send(DISPLAY_NAME_STORAGE_KEY)
+        .map_err(|error| anyhow!("Failed to load display name: {error}"))?;
+    let display_name: Option<String> = eval
+        .join()
+        .await
+        .map_err(|error| anyhow!("Failed to load display name: {error}"))?;
+    Ok(display_name.filter(|display_name| !display_name.is_empty()))
+}
+
+fn store_display_name(display_name: &str) -> Result<()> {
+    let eval = document::eval(
+        r#"
+        const [storageKey, displayName] = await dioxus.recv();
+        if (displayName) {
+            localStorage.setItem(storageKey, displayName);
+        } else {
+            localStorage.removeItem(storageKey);
+        }
+        "#,
+    );
+    eval.send((DISPLAY_NAME_STORAGE_KEY, display_name))
+        .map_err(|error| anyhow!("Failed to store display name: {error}"))
+}
+
+pub enum PresenceCommand {
+    /// Sends our presence to a peer which has just connected.
+    AnnounceTo {
+        remote_node_id: NodeId,
+    },
+    ApplyEphemeralMessage {
+        remote_node_id: NodeId,
+        message: EphemeralMessage,
+    },
+    Follow {
+        remote_node_id: NodeId,
+    },
+    /// Sends our presence to all peers, after we opened another file.
+    AnnounceToAll,
+    SetDisplayName {
+        name: String,
+    },
+}
+
+struct LocalPresence {
+    sequence_number: usize,
+}
+
+impl LocalPresence {
+    fn next_message(&mut self) -> Option<EphemeralMessage> {
+        let node_id = NODE_INFO.read().as_ref()?.node_id;
+        let file_path = ACTIVE_FILE.read().clone().unwrap_or_default();
+
+        self.sequence_number += 1;
+        Some(EphemeralMessage {
+            cursor_id: node_id.to_string(),
+            sequence_number: self.sequence_number,
+            cursor_state: CursorState {
+                name: LOCAL_DISPLAY_NAME.read().clone(),
+                file_path: RelativePath::new(file_path),
+                ranges: Vec::new(),
+            },
+        })
+    }
+}
+
+fn announce_to_all(presence: &mut LocalPresence, connection_service: Coroutine<ConnectionCommand>) {
+    let remote_node_ids: Vec<NodeId> = CONNECTED_PEERS
+        .read()
+        .iter()
+        .map(|peer| peer.node_id)
+        .collect();
+    for remote_node_id in remote_node_ids {
+        if let Some(message) = presence.next_message() {
+            connection_service.send(ConnectionCommand::SendEphemeralMessage {
+                remote_node_id,
+                message,
+            });
+        }
+    }
+}
+
+fn handle_presence_command(
+    presence: &mut LocalPresence,
+    command: PresenceCommand,
+    automerge_service: Coroutine<AutomergeCommand>,
+    connection_service: Coroutine<ConnectionCommand>,
+) {
+    match command {
+        PresenceCommand::AnnounceTo { remote_node_id } => {
+            if let Some(message) = presence.next_message() {
+                connection_service.send(ConnectionCommand::SendEphemeralMessage {
+                    remote_node_id,
+                    message,
+                });
+            }
+        }
+        PresenceCommand::ApplyEphemeralMessage {
+            remote_node_id,
+            message,
+        } => {
+            let mut peer_cursors = PEER_CURSORS.write();
+            let key = (remote_node_id, message.cursor_id.clone());
+            let is_outdated = peer_cursors
+                .get(&key)
+                .is_some_and(|cursor| cursor.sequence_number >= message.sequence_number);
+            if !is_outdated {
+                peer_cursors.insert(
+                    key,
+                    PeerCursor {
+                        cursor_id: message.cursor_id,
+                        sequence_number: message.sequence_number,
+                        cursor_state: message.cursor_state,
+                        received_at: Local::now(),
+                    },
+                );
+                drop(peer_cursors);
+
+                if *FOLLOWED_PEER.read() == Some(remote_node_id) {
+                    open_followed_file(automerge_service);
+                }
+            }
+        }
+        PresenceCommand::Follow { remote_node_id } => {
+            *FOLLOWED_PEER.write() = Some(remote_node_id);
+            open_followed_file(automerge_service);
+        }
+        PresenceCommand::AnnounceToAll => {
+            announce_to_all(presence, connection_service);
+        }
+        PresenceCommand::SetDisplayName { name } => {
+            let name = name.trim();
+            *LOCAL_DISPLAY_NAME.write() = (!name.is_empty()).then(|| name.to_string());
+            if let Err(error) = store_display_name(name) {
+                log_event(PresenceEvent::Error { error });
+            }
+            announce_to_all(presence, connection_service);
+        }
+    }
+}
+
+pub async fn start_presence_service(mut commands_rx: UnboundedReceiver<PresenceCommand>) {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let connection_service = use_coroutine_handle::<ConnectionCommand>();
+
+    let mut presence = LocalPresence { sequence_number: 0 };
+
+    match load_display_name().await {
+        Ok(display_name) => *LOCAL_DISPLAY_NAME.write() = display_name,
+        Err(error) => log_event(PresenceEvent::Error { error }),
+    }
+
+    while let Some(command) = commands_rx.next().await {
+        handle_presence_command(
+            &mut presence,
+            command,
+            automerge_service,
+            connection_service,
+        );
+    }
+}
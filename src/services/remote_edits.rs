@@ -0,0 +1,128 @@
+use dioxus::prelude::{GlobalSignal, Signal};
+use std::fmt::Display;
+
+/// Lines of a file, counted from 1 like editors do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineRange {
+    pub first: usize,
+    pub last: usize,
+}
+
+impl LineRange {
+    pub fn contains(&self, line_number: usize) -> bool {
+        (self.first..=self.last).contains(&line_number)
+    }
+
+    /// Whether the line is at most `distance` lines away from the range.
+    pub fn is_near(&self, line_number: usize, distance: usize) -> bool {
+        line_number + distance >= self.first && line_number <= self.last + distance
+    }
+}
+
+impl Display for LineRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}", self.first)
+        } else {
+            write!(f, "{}–{}", self.first, self.last)
+        }
+    }
+}
+
+/// Lines touched by a sequence of splices into a text, in the numbering after the last splice.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChangedLines {
+    ranges: Vec<LineRange>,
+}
+
+impl ChangedLines {
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Records replacing `delete` characters at the character `index` of `text` with `insert`,
+    /// where `text` is the content before the splice.
+    pub fn record_splice(&mut self, text: &str, index: usize, delete: usize, insert: &str) {
+        let mut characters = text.chars();
+        let line = 1 + characters
+            .by_ref()
+            .take(index)
+            .filter(|c| *c == '\n')
+            .count();
+        let deleted_lines = characters.take(delete).filter(|c| *c == '\n').count();
+        let inserted_lines = insert.matches('\n').count();
+
+        // Ranges after the splice move, ranges overlapping it are merged into it.
+        let mut changed = LineRange {
+            first: line,
+            last: line + inserted_lines,
+        };
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 1);
+        for range in self.ranges.drain(..) {
+            if range.last < line {
+                ranges.push(range);
+            } else if range.first > line + deleted_lines {
+                ranges.push(LineRange {
+                    first: range.first + inserted_lines - deleted_lines,
+                    last: range.last + inserted_lines - deleted_lines,
+                });
+            } else {
+                changed.first = changed.first.min(range.first);
+                changed.last = changed
+                    .last
+                    .max((range.last + inserted_lines).saturating_sub(deleted_lines));
+            }
+        }
+        ranges.push(changed);
+        ranges.sort_by_key(|range| range.first);
+
+        // Adjacent ranges read better as one.
+        for range in ranges {
+            match self.ranges.last_mut() {
+                Some(previous) if range.first <= previous.last + 1 => {
+                    previous.last = previous.last.max(range.last);
+                }
+                _ => self.ranges.push(range),
+            }
+        }
+    }
+
+    pub fn ranges(&self) -> &[LineRange] {
+        &self.ranges
+    }
+}
+
+/// Changes which peers made to the active file, shown until they fade out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteEdit {
+    pub file_name: String,
+    pub authors: Vec<String>,
+    pub lines: Vec<LineRange>,
+    pub received_at_millis: i64,
+}
+
+impl RemoteEdit {
+    pub fn touches_line(&self, line_number: usize) -> bool {
+        self.lines.iter().any(|range| range.contains(line_number))
+    }
+}
+
+impl Display for RemoteEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines = self
+            .lines
+            .iter()
+            .map(LineRange::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let single_line = self.lines.len() == 1 && self.lines[0].first == self.lines[0].last;
+        write!(
+            f,
+            "{} edited {} {lines}",
+            self.authors.join(", "),
+            if single_line { "line" } else { "lines" }
+        )
+    }
+}
+
+pub static REMOTE_EDIT: GlobalSignal<Option<RemoteEdit>> = Signal::global(|| None);
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use dioxus::prelude::{GlobalSignal, Signal};
+use regex::{Captures, Regex, RegexBuilder};
+use std::collections::BTreeMap;
+
+/// Results beyond this number are dropped, so a pattern like `.` doesn't freeze the UI.
+pub const MAX_SEARCH_RESULTS: usize = 1000;
+
+#[derive(Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub case_sensitive: bool,
+    pub regex: bool,
+}
+
+impl SearchQuery {
+    fn matcher(&self) -> Result<Regex> {
+        let pattern = if self.regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        Ok(RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()?)
+    }
+
+    /// Text a match gets replaced with, capture groups like `$1` are only expanded for regexes.
+    fn expand(&self, captures: &Captures, replacement: &str) -> String {
+        if !self.regex {
+            return replacement.to_owned();
+        }
+
+        let mut expanded = String::new();
+        captures.expand(replacement, &mut expanded);
+        expanded
+    }
+
+    /// Returns the splices replacing all matches in `content`, from the last to the first one, so
+    /// they can be applied in order without shifting each other.
+    pub fn splices(&self, content: &str, replacement: &str) -> Result<Vec<TextSplice>> {
+        let matcher = self.matcher()?;
+        let mut splices = Vec::new();
+        let mut position = 0;
+        let mut char_position = 0;
+
+        for captures in matcher.captures_iter(content) {
+            let found = captures.get(0).expect("capture group 0 is the whole match");
+            if found.is_empty() {
+                continue;
+            }
+
+            char_position += content[position..found.start()].chars().count();
+            position = found.start();
+            splices.push(TextSplice {
+                index: char_position,
+                deleted: found.as_str().to_owned(),
+                inserted: self.expand(&captures, replacement),
+            });
+        }
+
+        splices.reverse();
+        Ok(splices)
+    }
+}
+
+/// Replacement of `deleted` at the character `index` with `inserted`, like Automerge splices text.
+///
+/// Keeping the deleted text instead of its length makes the splice invertible.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextSplice {
+    pub index: usize,
+    pub deleted: String,
+    pub inserted: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub file_name: String,
+    /// Starts at 1, like in editors.
+    pub line_number: usize,
+    pub line: String,
+    /// Byte range of the match within `line`, cut off at the end of the line.
+    pub line_match: (usize, usize),
+    /// UTF-16 offsets of the match within the file, as used by text areas in the browser.
+    pub selection_start: usize,
+    pub selection_end: usize,
+}
+
+/// Content of every file in the document, kept up to date from the patches of sync messages.
+///
+/// The contents are only read once a search needs them, as large documents contain thousands of
+/// files which might never be searched.
+#[derive(Default)]
+pub struct SearchIndex {
+    files: BTreeMap<String, String>,
+    complete: bool,
+}
+
+impl SearchIndex {
+    /// Drops all contents, until the index is built again.
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.complete = false;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn mark_complete(&mut self) {
+        self.complete = true;
+    }
+
+    pub fn insert_file(&mut self, file_name: &str, content: String) {
+        self.files.insert(file_name.to_owned(), content);
+    }
+
+    pub fn remove_file(&mut self, file_name: &str) {
+        self.files.remove(file_name);
+    }
+
+    pub fn file_content_mut(&mut self, file_name: &str) -> Option<&mut String> {
+        self.files.get_mut(file_name)
+    }
+
+    /// Returns the matches in all files, ordered by file name and position.
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        Ok(self
+            .find(query, None)?
+            .into_iter()
+            .map(|(result, _)| result)
+            .collect())
+    }
+
+    /// Like [`SearchIndex::search`], together with the line as it would look after replacing the
+    /// match with `replacement`.
+    pub fn preview_replacements(
+        &self,
+        query: &SearchQuery,
+        replacement: &str,
+    ) -> Result<Vec<(SearchResult, String)>> {
+        self.find(query, Some(replacement))
+    }
+
+    fn find(
+        &self,
+        query: &SearchQuery,
+        replacement: Option<&str>,
+    ) -> Result<Vec<(SearchResult, String)>> {
+        let mut results = Vec::new();
+        if query.pattern.is_empty() {
+            return Ok(results);
+        }
+
+        let matcher = query.matcher()?;
+        for (file_name, content) in &self.files {
+            // Walk through the file once instead of counting from the start for every match.
+            let mut position = 0;
+            let mut line_number = 1;
+            let mut utf16_position = 0;
+
+            for captures in matcher.captures_iter(content) {
+                let found = captures.get(0).expect("capture group 0 is the whole match");
+                if found.is_empty() {
+                    continue;
+                }
+                if results.len() >= MAX_SEARCH_RESULTS {
+                    return Ok(results);
+                }
+
+                let skipped = &content[position..found.start()];
+                line_number += skipped.matches('\n').count();
+                utf16_position += skipped.encode_utf16().count();
+                position = found.start();
+
+                let line_start = content[..found.start()].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = content[found.start()..]
+                    .find('\n')
+                    .map_or(content.len(), |i| found.start() + i);
+
+                let replaced_line = match replacement {
+                    Some(replacement) => {
+                        let line_after = &content[found.end().min(line_end)..line_end];
+                        content[line_start..found.start()].to_owned()
+                            + &query.expand(&captures, replacement)
+                            + line_after
+                    }
+                    None => String::new(),
+                };
+
+                let result = SearchResult {
+                    file_name: file_name.clone(),
+                    line_number,
+                    line: content[line_start..line_end].to_owned(),
+                    line_match: (
+                        found.start() - line_start,
+                        found.end().min(line_end) - line_start,
+                    ),
+                    selection_start: utf16_position,
+                    selection_end: utf16_position + found.as_str().encode_utf16().count(),
+                };
+                results.push((result, replaced_line));
+            }
+        }
+        Ok(results)
+    }
+}
+
+pub static SEARCH_INDEX: GlobalSignal<SearchIndex> = Signal::global(SearchIndex::default);
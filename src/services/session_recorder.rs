@@ -0,0 +1,103 @@
+use crate::services::connection_service::{PeerMessage, PeerRole};
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use dioxus::prelude::{GlobalSignal, Readable, Signal};
+use iroh::NodeId;
+use postcard::{from_bytes, to_allocvec};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Version of the recording file format, bumped on incompatible changes.
+const RECORDING_VERSION: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FrameDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// A single `PeerMessage` as it went over the wire.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub timestamp_millis: i64,
+    pub remote_node_id: NodeId,
+    pub role: PeerRole,
+    pub direction: FrameDirection,
+    /// Postcard encoded `PeerMessage`, without the length prefix.
+    pub frame: Vec<u8>,
+}
+
+impl RecordedFrame {
+    pub fn peer_message(&self) -> Result<PeerMessage> {
+        from_bytes(&self.frame).context("Failed to convert recorded frame to PeerMessage")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecording {
+    pub version: u32,
+    pub started_at_millis: i64,
+    /// Saved document when the recording started, the incoming frames apply on top of it.
+    pub initial_doc: Vec<u8>,
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl SessionRecording {
+    pub fn start(initial_doc: Vec<u8>) -> Self {
+        Self {
+            version: RECORDING_VERSION,
+            started_at_millis: Local::now().timestamp_millis(),
+            initial_doc,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(to_allocvec(self)?)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let recording: Self = from_bytes(bytes).context("Failed to decode session recording")?;
+        if recording.version != RECORDING_VERSION {
+            bail!(
+                "session recording version {} is not supported (expected {RECORDING_VERSION})!",
+                recording.version
+            )
+        }
+        Ok(recording)
+    }
+}
+
+/// The recording in progress, frames are only captured while this is set.
+pub static SESSION_RECORDING: GlobalSignal<Option<SessionRecording>> = Signal::global(|| None);
+
+/// Result of replaying a recording, kept apart from the live document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayedRecording {
+    pub frame_count: usize,
+    pub file_contents: BTreeMap<String, String>,
+}
+
+pub static REPLAYED_RECORDING: GlobalSignal<Option<ReplayedRecording>> = Signal::global(|| None);
+
+pub fn record_frame(
+    remote_node_id: NodeId,
+    role: PeerRole,
+    direction: FrameDirection,
+    frame: &[u8],
+) {
+    // Avoid notifying subscribers of the signal for every frame while not recording.
+    if SESSION_RECORDING.read().is_none() {
+        return;
+    }
+
+    if let Some(recording) = SESSION_RECORDING.write().as_mut() {
+        recording.frames.push(RecordedFrame {
+            timestamp_millis: Local::now().timestamp_millis(),
+            remote_node_id,
+            role,
+            direction,
+            frame: frame.to_vec(),
+        });
+    }
+}
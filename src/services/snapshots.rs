@@ -0,0 +1,138 @@
+use crate::services::search_index::TextSplice;
+use anyhow::{anyhow, Context, Result};
+use automerge::ChangeHash;
+use derive_more::Display;
+use dioxus::prelude::{document, GlobalSignal, Signal};
+use serde::{Deserialize, Serialize};
+use similar::{DiffTag, TextDiff};
+
+/// Local storage entry keeping the snapshots, as they are only save points of this browser.
+const SNAPSHOTS_STORAGE_KEY: &str = "ethersync-web.snapshots";
+
+/// Named heads of the document, which can be compared with and restored later.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub created_at_millis: i64,
+    pub heads: Vec<ChangeHash>,
+}
+
+pub static SNAPSHOTS: GlobalSignal<Vec<Snapshot>> = Signal::global(Vec::new);
+
+pub async fn load_snapshots() -> Result<Vec<Snapshot>> {
+    let eval = document::eval(
+        r#"
+        const storageKey = await dioxus.recv();
+        return localStorage.getItem(storageKey);
+        "#,
+    );
+    eval.send(SNAPSHOTS_STORAGE_KEY)
+        .map_err(|error| anyhow!("Failed to load snapshots: {error}"))?;
+    let snapshots: Option<String> = eval
+        .join()
+        .await
+        .map_err(|error| anyhow!("Failed to load snapshots: {error}"))?;
+
+    match snapshots {
+        Some(snapshots) => {
+            serde_json::from_str(&snapshots).context("Failed to parse stored snapshots")
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn store_snapshots(snapshots: &[Snapshot]) -> Result<()> {
+    let eval = document::eval(
+        r#"
+        const [storageKey, snapshots] = await dioxus.recv();
+        localStorage.setItem(storageKey, snapshots);
+        "#,
+    );
+    eval.send((SNAPSHOTS_STORAGE_KEY, serde_json::to_string(snapshots)?))
+        .map_err(|error| anyhow!("Failed to store snapshots: {error}"))
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum FileChange {
+    #[display("added")]
+    Added,
+    #[display("deleted")]
+    Deleted,
+    #[display("modified")]
+    Modified,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileComparison {
+    pub file_name: String,
+    pub change: FileChange,
+    pub unified_diff: String,
+}
+
+/// Files which changed since a snapshot was taken.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotComparison {
+    pub snapshot_name: String,
+    pub files: Vec<FileComparison>,
+}
+
+pub static SNAPSHOT_COMPARISON: GlobalSignal<Option<SnapshotComparison>> = Signal::global(|| None);
+
+impl FileComparison {
+    /// Compares the content of a file in a snapshot with its current content, `None` meaning
+    /// the file doesn't exist.
+    pub fn new(
+        file_name: &str,
+        snapshot_content: Option<&str>,
+        current_content: Option<&str>,
+    ) -> Option<Self> {
+        let change = match (snapshot_content, current_content) {
+            (None, None) => return None,
+            (Some(snapshot_content), Some(current_content))
+                if snapshot_content == current_content =>
+            {
+                return None
+            }
+            (None, Some(_)) => FileChange::Added,
+            (Some(_), None) => FileChange::Deleted,
+            (Some(_), Some(_)) => FileChange::Modified,
+        };
+
+        let unified_diff = TextDiff::from_lines(
+            snapshot_content.unwrap_or_default(),
+            current_content.unwrap_or_default(),
+        )
+        .unified_diff()
+        .header("snapshot", "current")
+        .to_string();
+
+        Some(Self {
+            file_name: file_name.to_owned(),
+            change,
+            unified_diff,
+        })
+    }
+}
+
+/// Returns the splices turning `current` into `target`, ordered from the last to the first one.
+pub fn splices_between(current: &str, target: &str) -> Vec<TextSplice> {
+    let current_chars: Vec<char> = current.chars().collect();
+    let target_chars: Vec<char> = target.chars().collect();
+
+    let diff = TextDiff::from_chars(current, target);
+    let mut splices: Vec<TextSplice> = diff
+        .ops()
+        .iter()
+        .filter_map(|op| {
+            let (tag, current_range, target_range) = op.as_tag_tuple();
+            (tag != DiffTag::Equal).then(|| TextSplice {
+                index: current_range.start,
+                deleted: current_chars[current_range].iter().collect(),
+                inserted: target_chars[target_range].iter().collect(),
+            })
+        })
+        .collect();
+
+    splices.reverse();
+    splices
+}
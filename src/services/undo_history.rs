@@ -0,0 +1,287 @@
+use crate::services::search_index::TextSplice;
+use anyhow::Result;
+use automerge::transaction::Transactable;
+use automerge::{AutoCommit, ChangeHash, Cursor, MoveCursor, ObjId, ReadDoc};
+use derive_more::Display;
+use dioxus::prelude::{GlobalSignal, Signal};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum UndoDirection {
+    #[display("undo")]
+    Undo,
+    #[display("redo")]
+    Redo,
+}
+
+/// Position between two characters, which stays in place while peers edit around it.
+enum Boundary {
+    /// Right before a character.
+    Before(Cursor),
+    /// Right after a character, with cursors moving in both directions to tell whether the
+    /// character is still there.
+    After {
+        moving_before: Cursor,
+        moving_after: Cursor,
+    },
+    Start,
+}
+
+impl Boundary {
+    /// Boundary right after the character at `index`.
+    fn after(doc: &AutoCommit, object_id: &ObjId, index: usize) -> Result<Self> {
+        Ok(Boundary::After {
+            moving_before: doc.get_cursor_moving(object_id, index, None, MoveCursor::Before)?,
+            moving_after: doc.get_cursor_moving(object_id, index, None, MoveCursor::After)?,
+        })
+    }
+
+    /// Anchors the boundary to the following character, or to the previous one at the end of the
+    /// text, so it stays in front of text appended by peers.
+    fn at(doc: &AutoCommit, object_id: &ObjId, index: usize) -> Result<Self> {
+        if index < doc.length(object_id) {
+            Ok(Boundary::Before(doc.get_cursor(object_id, index, None)?))
+        } else if index > 0 {
+            Boundary::after(doc, object_id, index - 1)
+        } else {
+            Ok(Boundary::Start)
+        }
+    }
+
+    fn position(&self, doc: &AutoCommit, object_id: &ObjId) -> Result<usize> {
+        match self {
+            Boundary::Before(cursor) => Ok(doc.get_cursor_position(object_id, cursor, None)?),
+            Boundary::After {
+                moving_before,
+                moving_after,
+            } => {
+                let before = doc.get_cursor_position(object_id, moving_before, None)?;
+                let after = doc.get_cursor_position(object_id, moving_after, None)?;
+                // Both cursors only agree while the character is still there, except at the start
+                // of the text, where nothing is left to move before a deleted character.
+                let deleted_at_start = after == 0
+                    && (doc.length(object_id) == 0
+                        || doc.get_cursor_moving(object_id, 0, None, MoveCursor::After)?
+                            != *moving_after);
+                Ok(if before == after && !deleted_at_start {
+                    after + 1
+                } else {
+                    after
+                })
+            }
+            Boundary::Start => Ok(0),
+        }
+    }
+}
+
+/// Splice of a local edit, which follows the text while peers change it.
+pub struct RecordedSplice {
+    /// Start of the inserted text, if anything was inserted.
+    start: Option<Boundary>,
+    /// End of the inserted text.
+    end: Boundary,
+    deleted: String,
+}
+
+/// Applies splices, which must be ordered from the last to the first one, to a text object.
+///
+/// Doesn't commit, so the splices of one operation end up in a single change.
+pub fn apply_splices(
+    doc: &mut AutoCommit,
+    object_id: &ObjId,
+    splices: &[TextSplice],
+) -> Result<Vec<RecordedSplice>> {
+    let mut recorded_splices = Vec::with_capacity(splices.len());
+
+    for splice in splices {
+        let inserted_length = splice.inserted.chars().count();
+        doc.splice_text(
+            object_id,
+            splice.index,
+            splice.deleted.chars().count() as isize,
+            &splice.inserted,
+        )?;
+
+        // Anchor inserted text to its own characters, so text of peers next to it stays outside.
+        let (start, end) = if inserted_length > 0 {
+            let start = doc.get_cursor(object_id, splice.index, None)?;
+            let last_index = splice.index + inserted_length - 1;
+            (
+                Some(Boundary::Before(start)),
+                Boundary::after(doc, object_id, last_index)?,
+            )
+        } else {
+            (None, Boundary::at(doc, object_id, splice.index)?)
+        };
+
+        recorded_splices.push(RecordedSplice {
+            start,
+            end,
+            deleted: splice.deleted.clone(),
+        });
+    }
+    Ok(recorded_splices)
+}
+
+/// A committed local change of a file.
+pub struct LocalEdit {
+    pub change_hash: Option<ChangeHash>,
+    splices: Vec<RecordedSplice>,
+}
+
+impl LocalEdit {
+    pub fn new(change_hash: Option<ChangeHash>, splices: Vec<RecordedSplice>) -> Self {
+        Self {
+            change_hash,
+            splices,
+        }
+    }
+
+    /// Returns the splices reverting this edit in the current text, ordered from the last to the
+    /// first one.
+    ///
+    /// Text inserted by peers in the meantime is kept, unless it ended up within text inserted by
+    /// this edit.
+    pub fn inverse_splices(&self, doc: &AutoCommit, object_id: &ObjId) -> Result<Vec<TextSplice>> {
+        let text = doc.text(object_id)?;
+        let byte_indices: Vec<usize> = text
+            .char_indices()
+            .map(|(byte_index, _)| byte_index)
+            .chain([text.len()])
+            .collect();
+
+        let mut splices = Vec::with_capacity(self.splices.len());
+        for recorded_splice in &self.splices {
+            let end = recorded_splice.end.position(doc, object_id)?;
+            let start = match &recorded_splice.start {
+                Some(start) => start.position(doc, object_id)?.min(end),
+                None => end,
+            };
+
+            // Boundaries past the end of the text can't be reverted.
+            let Some(&end_byte_index) = byte_indices.get(end) else {
+                continue;
+            };
+            let splice = TextSplice {
+                index: start,
+                deleted: text[byte_indices[start]..end_byte_index].to_owned(),
+                inserted: recorded_splice.deleted.clone(),
+            };
+            if !splice.deleted.is_empty() || !splice.inserted.is_empty() {
+                splices.push(splice);
+            }
+        }
+
+        splices.sort_by_key(|splice| Reverse(splice.index));
+        Ok(splices)
+    }
+}
+
+/// Local edits of a single file, changes of peers are never recorded here.
+#[derive(Default)]
+pub struct UndoHistory {
+    undo_stack: Vec<LocalEdit>,
+    redo_stack: Vec<LocalEdit>,
+}
+
+impl UndoHistory {
+    /// Records a new local edit, which makes undone edits unavailable for redo.
+    pub fn record(&mut self, edit: LocalEdit) {
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+    }
+
+    pub fn pop(&mut self, direction: UndoDirection) -> Option<LocalEdit> {
+        match direction {
+            UndoDirection::Undo => self.undo_stack.pop(),
+            UndoDirection::Redo => self.redo_stack.pop(),
+        }
+    }
+
+    /// Records the edit reverting an edit popped in `direction`, so it can be reverted again.
+    pub fn push_reverted(&mut self, direction: UndoDirection, edit: LocalEdit) {
+        match direction {
+            UndoDirection::Undo => self.redo_stack.push(edit),
+            UndoDirection::Redo => self.undo_stack.push(edit),
+        }
+    }
+
+    pub fn depth(&self) -> UndoDepth {
+        UndoDepth {
+            undo: self.undo_stack.len(),
+            redo: self.redo_stack.len(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UndoDepth {
+    pub undo: usize,
+    pub redo: usize,
+}
+
+/// Number of local edits which can be undone and redone per file.
+pub static UNDO_DEPTHS: GlobalSignal<HashMap<String, UndoDepth>> = Signal::global(HashMap::new);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use automerge::ObjType;
+
+    fn local_edit(doc: &mut AutoCommit, object_id: &ObjId, splice: TextSplice) -> LocalEdit {
+        let recorded_splices = apply_splices(doc, object_id, &[splice]).unwrap();
+        LocalEdit::new(doc.commit(), recorded_splices)
+    }
+
+    #[test]
+    fn text_inserted_at_the_start_and_deleted_by_peers_is_not_reverted() {
+        for peer_text in ["", "xyz"] {
+            let mut doc = AutoCommit::new();
+            let object_id = doc
+                .put_object(automerge::ROOT, "text", ObjType::Text)
+                .unwrap();
+            doc.splice_text(&object_id, 0, 0, peer_text).unwrap();
+            let edit = local_edit(
+                &mut doc,
+                &object_id,
+                TextSplice {
+                    index: 0,
+                    deleted: String::new(),
+                    inserted: "a".to_string(),
+                },
+            );
+            // A peer deletes the inserted character.
+            doc.splice_text(&object_id, 0, 1, "").unwrap();
+
+            assert!(edit.inverse_splices(&doc, &object_id).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn text_inserted_at_the_start_is_reverted() {
+        let mut doc = AutoCommit::new();
+        let object_id = doc
+            .put_object(automerge::ROOT, "text", ObjType::Text)
+            .unwrap();
+        doc.splice_text(&object_id, 0, 0, "xyz").unwrap();
+        let edit = local_edit(
+            &mut doc,
+            &object_id,
+            TextSplice {
+                index: 0,
+                deleted: String::new(),
+                inserted: "a".to_string(),
+            },
+        );
+
+        assert_eq!(
+            edit.inverse_splices(&doc, &object_id).unwrap(),
+            vec![TextSplice {
+                index: 0,
+                deleted: "a".to_string(),
+                inserted: String::new(),
+            }]
+        );
+    }
+}
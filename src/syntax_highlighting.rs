@@ -0,0 +1,569 @@
+//! Small line based syntax highlighter.
+//!
+//! Lines are highlighted one after another, carrying over the state of multi-line constructs
+//! like block comments. Results are cached per line and incoming state, so re-highlighting after
+//! a remote edit only tokenizes the lines which actually changed.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Language {
+    JavaScript,
+    Json,
+    Markdown,
+    Python,
+    Rust,
+    Toml,
+    TypeScript,
+    Yaml,
+}
+
+impl Language {
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let (_, extension) = file_name.rsplit_once('.')?;
+        match extension.to_lowercase().as_str() {
+            "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "json" => Some(Language::Json),
+            "md" | "markdown" => Some(Language::Markdown),
+            "py" | "pyi" => Some(Language::Python),
+            "rs" => Some(Language::Rust),
+            "toml" => Some(Language::Toml),
+            "ts" | "tsx" | "mts" | "cts" => Some(Language::TypeScript),
+            "yaml" | "yml" => Some(Language::Yaml),
+            _ => None,
+        }
+    }
+
+    fn syntax(self) -> Syntax {
+        match self {
+            Language::JavaScript | Language::TypeScript => Syntax {
+                line_comment: Some("//"),
+                block_comment: Some(("/*", "*/")),
+                quotes: &['"', '\'', '`'],
+                triple_quotes: false,
+                keywords: if self == Language::TypeScript {
+                    TYPESCRIPT_KEYWORDS
+                } else {
+                    JAVASCRIPT_KEYWORDS
+                },
+                literals: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+                keys: false,
+            },
+            Language::Json => Syntax {
+                line_comment: None,
+                block_comment: None,
+                quotes: &['"'],
+                triple_quotes: false,
+                keywords: &[],
+                literals: &["true", "false", "null"],
+                keys: true,
+            },
+            Language::Markdown => Syntax {
+                line_comment: None,
+                block_comment: None,
+                quotes: &[],
+                triple_quotes: false,
+                keywords: &[],
+                literals: &[],
+                keys: false,
+            },
+            Language::Python => Syntax {
+                line_comment: Some("#"),
+                block_comment: None,
+                quotes: &['"', '\''],
+                triple_quotes: true,
+                keywords: PYTHON_KEYWORDS,
+                literals: &["True", "False", "None"],
+                keys: false,
+            },
+            Language::Rust => Syntax {
+                line_comment: Some("//"),
+                block_comment: Some(("/*", "*/")),
+                quotes: &['"'],
+                triple_quotes: false,
+                keywords: RUST_KEYWORDS,
+                literals: &["true", "false"],
+                keys: false,
+            },
+            Language::Toml => Syntax {
+                line_comment: Some("#"),
+                block_comment: None,
+                quotes: &['"', '\''],
+                triple_quotes: true,
+                keywords: &[],
+                literals: &["true", "false", "inf", "nan"],
+                keys: true,
+            },
+            Language::Yaml => Syntax {
+                line_comment: Some("#"),
+                block_comment: None,
+                quotes: &['"', '\''],
+                triple_quotes: false,
+                keywords: &[],
+                literals: &["true", "false", "null", "yes", "no", "on", "off", "~"],
+                keys: true,
+            },
+        }
+    }
+}
+
+const JAVASCRIPT_KEYWORDS: &[&str] = &[
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "finally",
+    "for",
+    "from",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "of",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+const TYPESCRIPT_KEYWORDS: &[&str] = &[
+    "abstract",
+    "any",
+    "as",
+    "async",
+    "await",
+    "boolean",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "declare",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "finally",
+    "for",
+    "from",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "keyof",
+    "let",
+    "namespace",
+    "never",
+    "new",
+    "number",
+    "of",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "return",
+    "static",
+    "string",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "type",
+    "typeof",
+    "unknown",
+    "var",
+    "void",
+    "while",
+    "yield",
+];
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+    "match", "case", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+    "yield",
+];
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "type", "unsafe", "use",
+    "where", "while",
+];
+
+/// What a language looks like to the tokenizer.
+struct Syntax {
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Whether `"""` and `'''` start strings which can span multiple lines.
+    triple_quotes: bool,
+    keywords: &'static [&'static str],
+    literals: &'static [&'static str],
+    /// Whether names followed by `:` or `=` are keys, like in configuration files.
+    keys: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Comment,
+    Heading,
+    Key,
+    Keyword,
+    Literal,
+    Number,
+    Plain,
+    String,
+}
+
+impl TokenKind {
+    pub fn css_class(self) -> &'static str {
+        match self {
+            TokenKind::Comment => "token-comment",
+            TokenKind::Heading => "token-heading",
+            TokenKind::Key => "token-key",
+            TokenKind::Keyword => "token-keyword",
+            TokenKind::Literal => "token-literal",
+            TokenKind::Number => "token-number",
+            TokenKind::Plain => "token-plain",
+            TokenKind::String => "token-string",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+impl Token {
+    pub fn plain(text: &str) -> Self {
+        Self {
+            kind: TokenKind::Plain,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// State carried from the end of one line to the start of the next one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+enum LineState {
+    #[default]
+    Normal,
+    BlockComment,
+    TripleQuotedString(char),
+    CodeFence,
+}
+
+struct LineTokenizer<'a> {
+    tokens: Vec<Token>,
+    line: &'a str,
+    position: usize,
+}
+
+impl<'a> LineTokenizer<'a> {
+    fn new(line: &'a str) -> Self {
+        Self {
+            tokens: Vec::new(),
+            line,
+            position: 0,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.position..]
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.line.len()
+    }
+
+    fn push(&mut self, kind: TokenKind, length: usize) {
+        if length == 0 {
+            return;
+        }
+        let text = &self.line[self.position..self.position + length];
+        self.position += length;
+        match self.tokens.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(text),
+            _ => self.tokens.push(Token {
+                kind,
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    /// Emits everything up to and including `end` as `kind`, returns whether `end` was found.
+    fn push_until(&mut self, kind: TokenKind, end: &str) -> bool {
+        match self.rest().find(end) {
+            Some(index) => {
+                self.push(kind, index + end.len());
+                true
+            }
+            None => {
+                self.push(kind, self.rest().len());
+                false
+            }
+        }
+    }
+
+    /// Length of a string starting with `quote` at the current position, up to the closing quote
+    /// or the end of the line.
+    fn quoted_length(&self, quote: char) -> usize {
+        let mut escaped = false;
+        for (index, character) in self.rest().char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if character == '\\' {
+                escaped = true;
+            } else if character == quote {
+                return index + character.len_utf8();
+            }
+        }
+        self.rest().len()
+    }
+
+    fn word_length(&self) -> usize {
+        self.rest()
+            .find(|character: char| !(character.is_alphanumeric() || character == '_'))
+            .unwrap_or(self.rest().len())
+    }
+
+    fn number_length(&self) -> usize {
+        self.rest()
+            .find(|character: char| {
+                !(character.is_ascii_alphanumeric() || character == '.' || character == '_')
+            })
+            .unwrap_or(self.rest().len())
+    }
+
+    fn follows_identifier(&self) -> bool {
+        self.line[..self.position]
+            .chars()
+            .next_back()
+            .is_some_and(|character| character.is_alphanumeric() || character == '_')
+    }
+
+    fn is_followed_by_key_separator(&self, length: usize) -> bool {
+        let after = self.rest()[length..].trim_start();
+        after.starts_with(':') || after.starts_with('=')
+    }
+}
+
+fn highlight_code_line(
+    syntax: &Syntax,
+    line: &str,
+    mut state: LineState,
+) -> (Vec<Token>, LineState) {
+    let mut tokenizer = LineTokenizer::new(line);
+
+    while !tokenizer.is_done() {
+        match state {
+            LineState::BlockComment => {
+                let (_, end) = syntax.block_comment.unwrap_or(("", ""));
+                if tokenizer.push_until(TokenKind::Comment, end) {
+                    state = LineState::Normal;
+                }
+                continue;
+            }
+            LineState::TripleQuotedString(quote) => {
+                if tokenizer.push_until(TokenKind::String, &quote.to_string().repeat(3)) {
+                    state = LineState::Normal;
+                }
+                continue;
+            }
+            LineState::Normal | LineState::CodeFence => {}
+        }
+
+        let rest = tokenizer.rest();
+        let character = rest.chars().next().unwrap_or_default();
+
+        if syntax
+            .line_comment
+            .is_some_and(|line_comment| rest.starts_with(line_comment))
+        {
+            tokenizer.push(TokenKind::Comment, rest.len());
+        } else if let Some((start, _)) = syntax
+            .block_comment
+            .filter(|(start, _)| rest.starts_with(start))
+        {
+            tokenizer.push(TokenKind::Comment, start.len());
+            state = LineState::BlockComment;
+        } else if syntax.triple_quotes
+            && syntax.quotes.contains(&character)
+            && rest.starts_with(&character.to_string().repeat(3))
+        {
+            tokenizer.push(TokenKind::String, 3);
+            state = LineState::TripleQuotedString(character);
+        } else if syntax.quotes.contains(&character) {
+            let length = tokenizer.quoted_length(character);
+            let kind = if syntax.keys && tokenizer.is_followed_by_key_separator(length) {
+                TokenKind::Key
+            } else {
+                TokenKind::String
+            };
+            tokenizer.push(kind, length);
+        } else if character.is_ascii_digit() && !tokenizer.follows_identifier() {
+            tokenizer.push(TokenKind::Number, tokenizer.number_length());
+        } else if character.is_alphabetic() || character == '_' || character == '~' {
+            let length = if character == '~' {
+                1
+            } else {
+                tokenizer.word_length()
+            };
+            let word = &rest[..length];
+            let kind = if syntax.keys && tokenizer.is_followed_by_key_separator(length) {
+                TokenKind::Key
+            } else if syntax.keywords.contains(&word) {
+                TokenKind::Keyword
+            } else if syntax.literals.contains(&word) {
+                TokenKind::Literal
+            } else {
+                TokenKind::Plain
+            };
+            tokenizer.push(kind, length);
+        } else if syntax.keys && character == '[' && rest.trim_end().ends_with(']') {
+            // TOML tables
+            tokenizer.push(TokenKind::Heading, rest.len());
+        } else {
+            tokenizer.push(TokenKind::Plain, character.len_utf8());
+        }
+    }
+
+    (tokenizer.tokens, state)
+}
+
+fn highlight_markdown_line(line: &str, state: LineState) -> (Vec<Token>, LineState) {
+    let mut tokenizer = LineTokenizer::new(line);
+    let trimmed = line.trim_start();
+
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+        tokenizer.push(TokenKind::Keyword, line.len());
+        let state = if state == LineState::CodeFence {
+            LineState::Normal
+        } else {
+            LineState::CodeFence
+        };
+        return (tokenizer.tokens, state);
+    }
+
+    if state == LineState::CodeFence {
+        tokenizer.push(TokenKind::String, line.len());
+        return (tokenizer.tokens, state);
+    }
+
+    if trimmed.starts_with('#') {
+        tokenizer.push(TokenKind::Heading, line.len());
+        return (tokenizer.tokens, state);
+    }
+
+    if trimmed.starts_with('>') {
+        tokenizer.push(TokenKind::Comment, line.len());
+        return (tokenizer.tokens, state);
+    }
+
+    let indentation = line.len() - trimmed.len();
+    if ["- ", "* ", "+ "]
+        .iter()
+        .any(|marker| trimmed.starts_with(marker))
+    {
+        tokenizer.push(TokenKind::Plain, indentation);
+        tokenizer.push(TokenKind::Keyword, 1);
+    }
+
+    while !tokenizer.is_done() {
+        let rest = tokenizer.rest();
+        let character = rest.chars().next().unwrap_or_default();
+        if character == '`' {
+            tokenizer.push(TokenKind::String, tokenizer.quoted_length('`'));
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            let marker = &rest[..2];
+            let length = rest[2..]
+                .find(marker)
+                .map_or(2, |index| index + 2 * marker.len());
+            tokenizer.push(TokenKind::Keyword, length);
+        } else {
+            tokenizer.push(TokenKind::Plain, character.len_utf8());
+        }
+    }
+
+    (tokenizer.tokens, state)
+}
+
+type HighlightedLine = (Vec<Token>, LineState);
+
+/// Highlights a whole text, reusing the results of lines which didn't change.
+pub struct Highlighter {
+    language: Language,
+    cache: HashMap<(LineState, String), HighlightedLine>,
+}
+
+impl Highlighter {
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn highlight_line(&self, line: &str, state: LineState) -> HighlightedLine {
+        match self.language {
+            Language::Markdown => highlight_markdown_line(line, state),
+            language => highlight_code_line(&language.syntax(), line, state),
+        }
+    }
+
+    /// Returns the tokens of every line of `text`.
+    pub fn highlight(&mut self, text: &str) -> Vec<Vec<Token>> {
+        let mut cache = HashMap::with_capacity(self.cache.len());
+        let mut lines = Vec::new();
+        let mut state = LineState::default();
+
+        for line in text.split('\n') {
+            let key = (state, line.to_string());
+            let highlighted_line = match self.cache.remove(&key) {
+                Some(highlighted_line) => highlighted_line,
+                None => self.highlight_line(line, state),
+            };
+            state = highlighted_line.1;
+            lines.push(highlighted_line.0.clone());
+            cache.insert(key, highlighted_line);
+        }
+
+        // Only keep the lines of the current text, so the cache doesn't grow with every edit.
+        self.cache = cache;
+        lines
+    }
+}
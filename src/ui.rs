@@ -1,6 +1,16 @@
 pub mod automerge_document_view;
+pub mod binary_file_view;
 pub mod connection_form;
 pub mod connection_view;
+pub mod diagnostics_view;
+pub mod download;
+pub mod event_log_view;
 pub mod file_content_view;
 pub mod file_list;
+pub mod markdown_preview;
 pub mod node_view;
+pub mod presence_list;
+pub mod search_view;
+pub mod session_recorder_view;
+pub mod snapshot_view;
+pub mod sync_status_badge;
@@ -1,5 +1,7 @@
-use crate::services::automerge_service::AUTOMERGE_EVENTS;
+use crate::services::document_schema::{DOCUMENT_STATES, SCHEMA_MISMATCHES};
+use crate::ui::binary_file_view::BinaryFileView;
 use crate::ui::file_list::FileList;
+use crate::ui::presence_list::PresenceList;
 use dioxus::prelude::*;
 
 #[component]
@@ -8,15 +10,21 @@ pub fn AutomergeDocumentView() -> Element {
         section {
             h2 { "Automerge Document" }
 
-            FileList {}
+            for mismatch in SCHEMA_MISMATCHES.read().iter() {
+                p { class: "log-warning", "{mismatch}" }
+            }
 
-            hr { }
+            FileList {}
+            PresenceList {}
+            BinaryFileView {}
 
-            ul {
-                for event in AUTOMERGE_EVENTS.iter() {
-                    li {
-                        for line in event.to_string().split('\n') {
-                            "{line}" br {}
+            if !DOCUMENT_STATES.read().is_empty() {
+                details {
+                    summary { "states" }
+                    dl {
+                        for (key, value) in DOCUMENT_STATES.read().iter() {
+                            dt { code { "{key}" } }
+                            dd { code { "{value}" } }
                         }
                     }
                 }
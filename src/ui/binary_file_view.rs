@@ -0,0 +1,71 @@
+use crate::services::automerge_service::SELECTED_BINARY_FILE;
+use crate::ui::download::download_file;
+use crate::ui::file_list::format_size;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use dioxus::prelude::*;
+
+/// MIME type of images the browser can show, guessed from the file extension.
+fn image_mime_type(file_name: &str) -> Option<&'static str> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    match extension.to_lowercase().as_str() {
+        "apng" | "png" => Some("image/png"),
+        "avif" => Some("image/avif"),
+        "bmp" => Some("image/bmp"),
+        "gif" => Some("image/gif"),
+        "ico" => Some("image/x-icon"),
+        "jpeg" | "jpg" => Some("image/jpeg"),
+        "svg" => Some("image/svg+xml"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+#[component]
+pub fn BinaryFileView() -> Element {
+    // Encoding can take a while for large files, so only do it when the content changes.
+    let image_url = use_memo(move || {
+        let selected_binary_file = SELECTED_BINARY_FILE.read();
+        let selected = selected_binary_file.as_ref()?;
+        let mime_type = image_mime_type(&selected.file_name)?;
+        Some(format!(
+            "data:{mime_type};base64,{}",
+            STANDARD.encode(&selected.content)
+        ))
+    });
+
+    let Some(selected) = SELECTED_BINARY_FILE.read().clone() else {
+        return rsx! {};
+    };
+    let size = format_size(selected.content.len());
+    let mime_type = image_mime_type(&selected.file_name).unwrap_or("application/octet-stream");
+
+    rsx! {
+        h3 {
+            "{selected.file_name} "
+            small { "(binary, {size})" }
+        }
+
+        fieldset {
+            button {
+                onclick: move |_| {
+                    download_file(&selected.file_name, mime_type, selected.content.clone());
+                },
+                "download"
+            }
+
+            button {
+                onclick: move |_| *SELECTED_BINARY_FILE.write() = None,
+                "close"
+            }
+        }
+
+        if let Some(image_url) = image_url() {
+            img {
+                class: "binary-file-preview",
+                alt: "preview",
+                src: "{image_url}",
+            }
+        }
+    }
+}
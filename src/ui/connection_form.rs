@@ -1,4 +1,4 @@
-use crate::services::node_service::{NodeCommand, SecretAddress};
+use crate::services::node_service::{NodeCommand, SecretAddress, CONNECTION_ATTEMPT};
 use dioxus::prelude::*;
 use std::string::ToString;
 
@@ -124,9 +124,25 @@ pub fn ConnectionForm() -> Element {
                     }
                 }
 
-                button {
-                    type: "submit",
-                    "connect"
+                match CONNECTION_ATTEMPT.read().as_ref() {
+                    None => rsx! {
+                        button {
+                            type: "submit",
+                            "connect"
+                        }
+                    },
+                    Some(attempt) => {
+                        let started_at = attempt.started_at.format("%H:%M:%S");
+                        rsx! {
+                            span { "{attempt.stage}… (since {started_at}) " }
+
+                            button {
+                                type: "button",
+                                onclick: move |_| node_service.send(NodeCommand::CancelConnectionAttempt),
+                                "cancel"
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -1,8 +1,121 @@
-use crate::services::connection_service::{CONNECTED_PEERS, CONNECTION_EVENTS};
+use crate::services::automerge_service::{AutomergeCommand, SYNC_STATUS};
+use crate::services::connection_service::{ConnectedPeer, ConnectionCommand, CONNECTED_PEERS};
+use crate::services::presence_service::{
+    peer_colour, peer_name, stop_following, PresenceCommand, FOLLOWED_PEER,
+};
+use crate::ui::sync_status_badge::SyncStatusBadge;
 use dioxus::prelude::*;
 
+#[component]
+fn ConnectedPeerView(peer: ConnectedPeer) -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let connection_service = use_coroutine_handle::<ConnectionCommand>();
+    let presence_service = use_coroutine_handle::<PresenceCommand>();
+
+    let remote_node_id = peer.node_id;
+    let is_followed = *FOLLOWED_PEER.read() == Some(remote_node_id);
+    let name = peer_name(remote_node_id);
+    let sync_status = SYNC_STATUS.read().get(&remote_node_id).copied();
+    let colour = peer_colour(remote_node_id);
+    let connection_type = peer
+        .connection_type
+        .as_ref()
+        .map_or("unknown".to_string(), ToString::to_string);
+    let round_trip_time = peer.round_trip_time.as_millis();
+    let last_sync_heads = peer
+        .last_sync_heads
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    rsx! {
+        li {
+            strong {
+                style: "color: {colour};",
+                "{name}"
+            }
+            " {peer.node_id} ({peer.role}) "
+
+            if let Some(sync_status) = sync_status {
+                SyncStatusBadge { sync_status }
+            }
+
+            button {
+                onclick: move |_| {
+                    document::eval(&format!(
+                        "navigator.clipboard.writeText('{remote_node_id}')"
+                    ));
+                },
+                "copy ID"
+            }
+
+            button {
+                onclick: move |_| {
+                    if is_followed {
+                        stop_following();
+                    } else {
+                        presence_service.send(PresenceCommand::Follow { remote_node_id });
+                    }
+                },
+                if is_followed { "stop following" } else { "follow" }
+            }
+
+            button {
+                onclick: move |_| {
+                    automerge_service.send(AutomergeCommand::ResetSync { remote_node_id });
+                    automerge_service.send(AutomergeCommand::StartSync { remote_node_id });
+                },
+                "resync"
+            }
+
+            button {
+                onclick: move |_| {
+                    connection_service.send(ConnectionCommand::Disconnect { remote_node_id });
+                },
+                "disconnect"
+            }
+
+            dl {
+                dt { "connected since:" }
+                dd { "{peer.connected_at}" }
+
+                dt { "connection:" }
+                dd { "{connection_type}" }
+
+                dt { "protocol:" }
+                dd { "{peer.protocol_version}" }
+
+                dt { "round-trip time:" }
+                dd { "{round_trip_time} ms" }
+
+                dt { "traffic:" }
+                dd {
+                    "{peer.messages_received} messages / {peer.bytes_received} bytes received, "
+                    "{peer.messages_sent} messages / {peer.bytes_sent} bytes sent"
+                }
+
+                dt { "last sync heads:" }
+                dd { "[{last_sync_heads}]" }
+            }
+        }
+    }
+}
+
 #[component]
 pub fn ConnectionView() -> Element {
+    use_effect(|| {
+        let followed_peer = *FOLLOWED_PEER.read();
+        if followed_peer.is_some_and(|followed_peer| {
+            !CONNECTED_PEERS
+                .read()
+                .iter()
+                .any(|peer| peer.node_id == followed_peer)
+        }) {
+            stop_following();
+        }
+    });
+
     rsx! {
         section {
             h2 { "Connected Peers" }
@@ -11,19 +124,14 @@ pub fn ConnectionView() -> Element {
                 p { "not connected" }
             } else {
                 ul {
-                    for node_id in CONNECTED_PEERS.iter() {
-                        li { "{node_id}" }
+                    for peer in CONNECTED_PEERS.iter() {
+                        ConnectedPeerView {
+                            key: "{peer.node_id}",
+                            peer: peer.clone()
+                        }
                     }
                 }
             }
-
-            hr { }
-
-            ul {
-                for event in CONNECTION_EVENTS.iter() {
-                    li { "{event}" }
-                }
-            }
         }
     }
 }
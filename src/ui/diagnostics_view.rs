@@ -0,0 +1,73 @@
+use crate::services::automerge_service::AutomergeCommand;
+use crate::services::diagnostics::{MessageStatistics, TimingStatistics, DIAGNOSTICS};
+use crate::ui::file_list::format_size;
+use chrono::TimeDelta;
+use dioxus::prelude::*;
+
+fn format_duration(duration: TimeDelta) -> String {
+    format!("{} ms", duration.num_milliseconds())
+}
+
+fn format_messages(messages: &MessageStatistics) -> String {
+    format!(
+        "{} messages, {} in total, largest {}",
+        messages.message_count,
+        format_size(messages.total_bytes),
+        format_size(messages.largest_bytes)
+    )
+}
+
+fn format_timing(timing: &TimingStatistics) -> String {
+    format!(
+        "{} in total, {} on average, longest {}",
+        format_duration(timing.total),
+        format_duration(timing.average()),
+        format_duration(timing.longest)
+    )
+}
+
+#[component]
+pub fn DiagnosticsView() -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let diagnostics = DIAGNOSTICS.read().clone();
+
+    rsx! {
+        section {
+            h2 { "Diagnostics" }
+
+            dl {
+                if let Some(document) = &diagnostics.document {
+                    dt { "document size:" }
+                    dd { "{format_size(document.saved_bytes)}" }
+
+                    dt { "changes:" }
+                    dd { "{document.change_count}" }
+
+                    dt { "actors:" }
+                    dd { "{document.actor_count}" }
+
+                    dt { "files:" }
+                    dd { "{document.file_count}" }
+
+                    dt { "text length:" }
+                    dd { "{document.text_length} characters" }
+                }
+
+                dt { "sent sync messages:" }
+                dd { "{format_messages(&diagnostics.sent_messages)}" }
+
+                dt { "received sync messages:" }
+                dd { "{format_messages(&diagnostics.received_messages)}" }
+
+                dt { "applying received messages:" }
+                dd { "{format_timing(&diagnostics.applying_messages)}" }
+            }
+
+            button {
+                // Saving the whole document takes a while, so it's not done on every change.
+                onclick: move |_| automerge_service.send(AutomergeCommand::RefreshDiagnostics),
+                if diagnostics.document.is_some() { "refresh document statistics" } else { "compute document statistics" }
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use dioxus::prelude::*;
+
+/// Lets the browser save `content` as a file, as there is no file system access from WASM.
+pub fn download_file(file_name: &str, mime_type: &str, content: Vec<u8>) {
+    let eval = document::eval(
+        r#"
+        const [fileName, mimeType, content] = await dioxus.recv();
+        const blob = new Blob([new Uint8Array(content)], { type: mimeType });
+        const url = URL.createObjectURL(blob);
+        const link = document.createElement("a");
+        link.href = url;
+        link.download = fileName;
+        link.click();
+        URL.revokeObjectURL(url);
+        "#,
+    );
+    let _ = eval.send((file_name, mime_type, content));
+}
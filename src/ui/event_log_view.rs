@@ -0,0 +1,136 @@
+use crate::services::event_log::{LogEntry, LogLevel, LogSource, EVENT_LOG};
+use crate::services::presence_service::peer_name;
+use crate::ui::download::download_file;
+use chrono::Local;
+use dioxus::prelude::*;
+use iroh::NodeId;
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq)]
+struct LogFilter {
+    source: Option<LogSource>,
+    minimum_level: LogLevel,
+    peer: Option<NodeId>,
+    search_text: String,
+}
+
+impl LogFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.source.is_none_or(|source| entry.source == source)
+            && entry.level >= self.minimum_level
+            && self.peer.is_none_or(|peer| entry.peer == Some(peer))
+            && (self.search_text.is_empty()
+                || entry
+                    .message
+                    .to_lowercase()
+                    .contains(&self.search_text.to_lowercase()))
+    }
+}
+
+fn export_log(entries: &[LogEntry]) {
+    let content = entries
+        .iter()
+        .map(|entry| entry.to_json_line() + "\n")
+        .collect::<String>();
+    let file_name = format!(
+        "ethersync-web-{}.jsonl",
+        Local::now().format("%Y-%m-%d-%H%M%S")
+    );
+    download_file(&file_name, "application/jsonl", content.into_bytes());
+}
+
+#[component]
+pub fn EventLogView() -> Element {
+    let mut filter = use_signal(|| LogFilter {
+        source: None,
+        minimum_level: LogLevel::Info,
+        peer: None,
+        search_text: String::new(),
+    });
+
+    let mut peers: Vec<NodeId> = Vec::new();
+    for peer in EVENT_LOG.read().iter().filter_map(|entry| entry.peer) {
+        if !peers.contains(&peer) {
+            peers.push(peer);
+        }
+    }
+
+    let entries: Vec<LogEntry> = EVENT_LOG
+        .read()
+        .iter()
+        .filter(|entry| filter.read().matches(entry))
+        .cloned()
+        .collect();
+    let exported_entries = entries.clone();
+
+    rsx! {
+        section {
+            h2 { "Event Log" }
+
+            fieldset {
+                select {
+                    oninput: move |event: FormEvent| {
+                        filter.write().source = LogSource::ALL
+                            .into_iter()
+                            .find(|source| source.to_string() == event.value());
+                    },
+                    option { value: "", "all sources" }
+                    for source in LogSource::ALL {
+                        option { value: "{source}", "{source}" }
+                    }
+                }
+
+                select {
+                    oninput: move |event: FormEvent| {
+                        if let Some(level) = LogLevel::ALL
+                            .into_iter()
+                            .find(|level| level.to_string() == event.value())
+                        {
+                            filter.write().minimum_level = level;
+                        }
+                    },
+                    for level in LogLevel::ALL {
+                        option {
+                            value: "{level}",
+                            selected: level == filter.read().minimum_level,
+                            "{level} and above"
+                        }
+                    }
+                }
+
+                select {
+                    oninput: move |event: FormEvent| {
+                        filter.write().peer = NodeId::from_str(&event.value()).ok();
+                    },
+                    option { value: "", "all peers" }
+                    for peer in peers {
+                        option { value: "{peer}", "{peer_name(peer)}" }
+                    }
+                }
+
+                input {
+                    placeholder: "search",
+                    value: "{filter.read().search_text}",
+                    oninput: move |event: FormEvent| filter.write().search_text = event.value(),
+                }
+
+                button {
+                    onclick: move |_| export_log(&exported_entries),
+                    "export as JSON Lines"
+                }
+            }
+
+            ul {
+                for entry in entries {
+                    li {
+                        class: "log-{entry.level}",
+                        "{entry.date_time} [{entry.source}] {entry.level}: "
+                        for line in entry.message.split('\n') {
+                            "{line}" br {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,24 +1,546 @@
-use crate::services::automerge_service::SELECTED_FILE;
+use crate::services::automerge_service::{
+    active_file, AutomergeCommand, ACTIVE_FILE, FILE_BLAME, FILE_NOTICE, OPEN_FILES,
+};
+use crate::services::node_service::NODE_INFO;
+use crate::services::presence_service::{
+    connected_peer_cursors, peer_colour, peer_cursors, peer_name, stop_following, FOLLOWED_PEER,
+};
+use crate::services::remote_edits::REMOTE_EDIT;
+use crate::services::undo_history::{UndoDirection, UNDO_DEPTHS};
+use crate::syntax_highlighting::{Highlighter, Language, Token};
+use crate::ui::markdown_preview::{
+    sync_preview_scroll_position, MarkdownPreview, SOURCE_ELEMENT_ID,
+};
+use async_std::task::sleep;
 use dioxus::prelude::*;
+use iroh::NodeId;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How long edits of peers stay highlighted.
+const REMOTE_EDIT_DURATION: Duration = Duration::from_secs(5);
+/// Edits at most this many lines away from the caret are pointed out as near it.
+const NEAR_CURSOR_LINES: usize = 3;
+
+/// Where the user was in a tab, restored when switching back to it.
+#[derive(Clone, Copy, Default, Deserialize)]
+struct TabViewState {
+    scroll_top: f64,
+    selection_start: usize,
+    selection_end: usize,
+}
+
+async fn read_tab_view_state() -> Option<TabViewState> {
+    document::eval(
+        r#"
+        const textarea = document.getElementById("file_content");
+        if (!textarea) {
+            return null;
+        }
+        return {
+            scroll_top: textarea.closest(".editor").scrollTop,
+            selection_start: textarea.selectionStart,
+            selection_end: textarea.selectionEnd,
+        };
+        "#,
+    )
+    .join::<Option<TabViewState>>()
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Text to select and scroll to once its file is in the active tab.
+#[derive(Clone, PartialEq)]
+pub struct TextSelection {
+    pub file_name: String,
+    pub line_number: usize,
+    pub selection_start: usize,
+    pub selection_end: usize,
+}
+
+pub static SELECTION_TO_REVEAL: GlobalSignal<Option<TextSelection>> = Signal::global(|| None);
+
+fn reveal_selection(selection: &TextSelection) {
+    let eval = document::eval(
+        r#"
+        const [lineNumber, selectionStart, selectionEnd] = await dioxus.recv();
+        const textarea = document.getElementById("file_content");
+        if (textarea) {
+            textarea.focus({ preventScroll: true });
+            textarea.setSelectionRange(selectionStart, selectionEnd);
+            const editor = textarea.closest(".editor");
+            const lineHeight = parseFloat(getComputedStyle(textarea).lineHeight);
+            editor.scrollTop = (lineNumber - 1) * lineHeight - editor.clientHeight / 2;
+        }
+        "#,
+    );
+    let _ = eval.send((
+        selection.line_number,
+        selection.selection_start,
+        selection.selection_end,
+    ));
+}
+
+fn restore_tab_view_state(tab_view_state: TabViewState) {
+    let eval = document::eval(
+        r#"
+        const [scrollTop, selectionStart, selectionEnd] = await dioxus.recv();
+        const textarea = document.getElementById("file_content");
+        if (textarea) {
+            textarea.setSelectionRange(selectionStart, selectionEnd);
+            textarea.closest(".editor").scrollTop = scrollTop;
+        }
+        "#,
+    );
+    let _ = eval.send((
+        tab_view_state.scroll_top,
+        tab_view_state.selection_start,
+        tab_view_state.selection_end,
+    ));
+}
+
+/// Scrolls the lines into view, unless they are visible already.
+fn keep_lines_in_view(first_line_number: usize, last_line_number: usize) {
+    let eval = document::eval(
+        r#"
+        const [firstLineNumber, lastLineNumber] = await dioxus.recv();
+        const textarea = document.getElementById("file_content");
+        if (textarea) {
+            const editor = textarea.closest(".editor");
+            const lineHeight = parseFloat(getComputedStyle(textarea).lineHeight);
+            const top = (firstLineNumber - 1) * lineHeight;
+            const bottom = lastLineNumber * lineHeight;
+            if (top < editor.scrollTop || bottom > editor.scrollTop + editor.clientHeight) {
+                editor.scrollTop = top - editor.clientHeight / 3;
+            }
+        }
+        "#,
+    );
+    let _ = eval.send((first_line_number, last_line_number));
+}
+
+/// Highlighted lines of a file, or a single plain token per line for unknown languages.
+fn highlight(
+    highlighters: &RefCell<HashMap<String, Highlighter>>,
+    file_name: &str,
+    content: &str,
+) -> Vec<Vec<Token>> {
+    let Some(language) = Language::from_file_name(file_name) else {
+        return content
+            .split('\n')
+            .map(|line| vec![Token::plain(line)])
+            .collect();
+    };
+
+    highlighters
+        .borrow_mut()
+        .entry(file_name.to_string())
+        .or_insert_with(|| Highlighter::new(language))
+        .highlight(content)
+}
+
+/// Part of a highlighted line, where the carets of peers sit between the tokens.
+enum LinePart {
+    Token(Token),
+    Caret(NodeId),
+}
+
+/// Splits the tokens of a line at the carets, given as character offsets into the line.
+fn insert_carets(line: Vec<Token>, mut carets: Vec<(usize, NodeId)>) -> Vec<LinePart> {
+    carets.sort_by_key(|(character, _)| *character);
+    let mut carets = carets.into_iter().peekable();
+    let mut parts = Vec::with_capacity(line.len());
+    let mut line_offset = 0;
+    for token in line {
+        let mut rest = token.text.as_str();
+        loop {
+            while let Some((_, node_id)) =
+                carets.next_if(|(character, _)| *character <= line_offset)
+            {
+                parts.push(LinePart::Caret(node_id));
+            }
+            let Some(&(character, _)) = carets.peek() else {
+                break;
+            };
+            let split_at = character - line_offset;
+            match rest.char_indices().nth(split_at) {
+                Some((byte_index, _)) => {
+                    parts.push(LinePart::Token(Token {
+                        kind: token.kind,
+                        text: rest[..byte_index].to_string(),
+                    }));
+                    rest = &rest[byte_index..];
+                    line_offset += split_at;
+                }
+                None => break,
+            }
+        }
+        line_offset += rest.chars().count();
+        parts.push(LinePart::Token(Token {
+            kind: token.kind,
+            text: rest.to_string(),
+        }));
+    }
+    // Carets past the end of the line stay at its end.
+    parts.extend(carets.map(|(_, node_id)| LinePart::Caret(node_id)));
+    parts
+}
 
 #[component]
 pub fn FileContentView() -> Element {
-    if let Some(selected_file) = SELECTED_FILE.read().as_ref() {
-        rsx! {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let mut tab_view_states = use_signal(HashMap::<String, TabViewState>::new);
+    // Not a signal, highlighting caches lines while rendering and must not trigger a rerender.
+    let highlighters = use_hook(|| Rc::new(RefCell::new(HashMap::<String, Highlighter>::new())));
+    let mut remote_edit_near_cursor = use_signal(|| false);
+
+    // Point out edits of peers close to the caret, and let the highlight fade after a while.
+    use_effect(move || {
+        let Some(remote_edit) = REMOTE_EDIT.read().clone() else {
+            return;
+        };
+        spawn(async move {
+            let caret_line_number = read_tab_view_state().await.zip(active_file()).map(
+                |(tab_view_state, active_file)| {
+                    // Selection offsets of the textarea count UTF-16 code units.
+                    1 + active_file
+                        .content
+                        .encode_utf16()
+                        .take(tab_view_state.selection_start)
+                        .filter(|code_unit| *code_unit == u16::from(b'\n'))
+                        .count()
+                },
+            );
+            remote_edit_near_cursor.set(caret_line_number.is_some_and(|line_number| {
+                remote_edit
+                    .lines
+                    .iter()
+                    .any(|range| range.is_near(line_number, NEAR_CURSOR_LINES))
+            }));
+
+            sleep(REMOTE_EDIT_DURATION).await;
+            if REMOTE_EDIT.peek().as_ref() == Some(&remote_edit) {
+                *REMOTE_EDIT.write() = None;
+            }
+        });
+    });
+
+    // Restore the scroll and caret position whenever another tab gets active, unless some text
+    // of it should be revealed.
+    use_effect(move || {
+        if let Some(file_name) = ACTIVE_FILE.read().as_ref() {
+            if let Some(selection) = SELECTION_TO_REVEAL
+                .read()
+                .as_ref()
+                .filter(|selection| selection.file_name == *file_name)
+            {
+                reveal_selection(selection);
+                return;
+            }
+
+            let tab_view_state = tab_view_states
+                .peek()
+                .get(file_name)
+                .copied()
+                .unwrap_or_default();
+            restore_tab_view_state(tab_view_state);
+        }
+    });
+
+    // Keep the cursor of the followed peer in view, once its file is in the active tab.
+    use_effect(move || {
+        let Some(followed_peer) = *FOLLOWED_PEER.read() else {
+            return;
+        };
+        let Some(cursor) = peer_cursors(followed_peer).into_iter().next() else {
+            return;
+        };
+        if cursor.file_name() != *ACTIVE_FILE.read() {
+            return;
+        }
+
+        let ranges = &cursor.cursor_state.ranges;
+        let first_line = ranges.iter().map(|range| range.start.line).min();
+        let last_line = ranges.iter().map(|range| range.end.line).max();
+        if let Some((first_line, last_line)) = first_line.zip(last_line) {
+            // Cursor positions count lines from 0.
+            keep_lines_in_view(first_line + 1, last_line + 1);
+        }
+    });
+
+    // Kept up to date while scrolling and moving the caret, as files also get active from outside
+    // of the tabs, e.g. from the file list, search results or followed peers.
+    let save_tab_view_state = move || async move {
+        let Some(file_name) = ACTIVE_FILE.peek().clone() else {
+            return;
+        };
+        let Some(tab_view_state) = read_tab_view_state().await else {
+            return;
+        };
+        // The textarea may show another file by now.
+        if ACTIVE_FILE.peek().as_ref() == Some(&file_name) {
+            tab_view_states.write().insert(file_name, tab_view_state);
+        }
+    };
+
+    let switch_tab = move |file_name: String| {
+        stop_following();
+        *SELECTION_TO_REVEAL.write() = None;
+        automerge_service.send(AutomergeCommand::SelectFile { file_name });
+    };
+
+    let revert_local_edit = move |direction: UndoDirection| {
+        if let Some(file_name) = ACTIVE_FILE.read().clone() {
+            automerge_service.send(match direction {
+                UndoDirection::Undo => AutomergeCommand::Undo { file_name },
+                UndoDirection::Redo => AutomergeCommand::Redo { file_name },
+            });
+        }
+    };
+
+    let open_file_names: Vec<String> = OPEN_FILES
+        .read()
+        .iter()
+        .map(|open_file| open_file.file_name.clone())
+        .collect();
+    let active_file_name = ACTIVE_FILE.read().clone();
+
+    highlighters
+        .borrow_mut()
+        .retain(|file_name, _| open_file_names.contains(file_name));
+    let highlighted_lines = active_file()
+        .map(|active_file| highlight(&highlighters, &active_file.file_name, &active_file.content))
+        .unwrap_or_default();
+    // Cursor positions count lines and characters from 0.
+    let mut carets_by_line = HashMap::<usize, Vec<(usize, NodeId)>>::new();
+    for (node_id, cursors) in connected_peer_cursors() {
+        for cursor in cursors {
+            if cursor.file_name() != active_file_name {
+                continue;
+            }
+            for range in &cursor.cursor_state.ranges {
+                carets_by_line
+                    .entry(range.end.line)
+                    .or_default()
+                    .push((range.end.character, node_id));
+            }
+        }
+    }
+    let line_parts: Vec<Vec<LinePart>> = highlighted_lines
+        .into_iter()
+        .enumerate()
+        .map(|(line_index, line)| {
+            insert_carets(line, carets_by_line.remove(&line_index).unwrap_or_default())
+        })
+        .collect();
+    let file_blame = FILE_BLAME
+        .read()
+        .clone()
+        .filter(|file_blame| active_file_name.as_ref() == Some(&file_blame.file_name));
+    let local_node_id = NODE_INFO.read().as_ref().map(|node_info| node_info.node_id);
+    let blame_label = move |node_id: NodeId| {
+        if Some(node_id) == local_node_id {
+            "you".to_string()
+        } else {
+            peer_name(node_id)
+        }
+    };
+    let is_markdown = active_file_name
+        .as_deref()
+        .and_then(Language::from_file_name)
+        == Some(Language::Markdown);
+    let followed_peer = *FOLLOWED_PEER.read();
+    let remote_edit = REMOTE_EDIT
+        .read()
+        .clone()
+        .filter(|remote_edit| active_file_name.as_ref() == Some(&remote_edit.file_name));
+    let undo_depth = active_file_name
+        .as_ref()
+        .and_then(|file_name| UNDO_DEPTHS.read().get(file_name).copied())
+        .unwrap_or_default();
+
+    rsx! {
+        if let Some(notice) = FILE_NOTICE.read().as_ref() {
+            p {
+                class: "notice",
+                "{notice} "
+                button {
+                    onclick: move |_| *FILE_NOTICE.write() = None,
+                    "dismiss"
+                }
+            }
+        }
+
+        if let Some(active_file) = active_file() {
             section {
                 h2 {
                     "File Content",
-                    code { "({selected_file.file_name})" }
+                    code { "({active_file.file_name})" }
+                }
+
+                nav {
+                    class: "tabs",
+                    for file_name in open_file_names {
+                        span {
+                            class: if active_file_name.as_ref() == Some(&file_name) { "tab active" } else { "tab" },
+                            a {
+                                href: "#",
+                                onclick: {
+                                    let file_name = file_name.clone();
+                                    move |_| switch_tab(file_name.clone())
+                                },
+                                "{file_name}"
+                            }
+                            button {
+                                title: "close",
+                                onclick: {
+                                    let file_name = file_name.clone();
+                                    move |_| {
+                                        stop_following();
+                                        tab_view_states.write().remove(&file_name);
+                                        automerge_service.send(AutomergeCommand::CloseFile {
+                                            file_name: file_name.clone()
+                                        });
+                                    }
+                                },
+                                "×"
+                            }
+                        }
+                    }
                 }
 
-                textarea {
-                    disabled: true,
-                    rows: 10,
-                    "{selected_file.content}"
+                if let Some(followed_peer) = followed_peer {
+                    p {
+                        class: "notice",
+                        "Following {peer_name(followed_peer)}, press any key to stop. "
+                        button {
+                            onclick: move |_| stop_following(),
+                            "stop following"
+                        }
+                    }
+                }
+
+                if let Some(remote_edit) = &remote_edit {
+                    p {
+                        class: if remote_edit_near_cursor() { "notice remote-edit-near-cursor" } else { "notice" },
+                        "{remote_edit}"
+                        if remote_edit_near_cursor() {
+                            " near your cursor"
+                        }
+                        " "
+                        button {
+                            onclick: move |_| *REMOTE_EDIT.write() = None,
+                            "dismiss"
+                        }
+                    }
+                }
+
+                fieldset {
+                    button {
+                        title: "Ctrl+Z",
+                        disabled: undo_depth.undo == 0,
+                        onclick: move |_| revert_local_edit(UndoDirection::Undo),
+                        "undo ({undo_depth.undo})"
+                    }
+                    button {
+                        title: "Ctrl+Shift+Z or Ctrl+Y",
+                        disabled: undo_depth.redo == 0,
+                        onclick: move |_| revert_local_edit(UndoDirection::Redo),
+                        "redo ({undo_depth.redo})"
+                    }
+                    button {
+                        onclick: {
+                            let file_name = active_file.file_name.clone();
+                            let showing_blame = file_blame.is_some();
+                            move |_| automerge_service.send(AutomergeCommand::ShowBlame {
+                                file_name: (!showing_blame).then(|| file_name.clone()),
+                            })
+                        },
+                        if file_blame.is_some() { "hide blame" } else { "blame" }
+                    }
+                }
+
+                div {
+                    class: "side-by-side",
+
+                    // The transparent textarea on top keeps selecting and copying text working.
+                    div {
+                        id: SOURCE_ELEMENT_ID,
+                        class: "editor",
+                        onscroll: move |_| {
+                            if is_markdown {
+                                sync_preview_scroll_position();
+                            }
+                            save_tab_view_state()
+                        },
+                        onkeyup: move |_| save_tab_view_state(),
+                        onmouseup: move |_| save_tab_view_state(),
+                        onkeydown: move |event: KeyboardEvent| {
+                            stop_following();
+                            let modifiers = event.modifiers();
+                            if !(modifiers.ctrl() || modifiers.meta()) {
+                                return;
+                            }
+                            let direction = match event.key() {
+                                Key::Character(character) if character.eq_ignore_ascii_case("z") => {
+                                    if modifiers.shift() { UndoDirection::Redo } else { UndoDirection::Undo }
+                                }
+                                Key::Character(character) if character.eq_ignore_ascii_case("y") => {
+                                    UndoDirection::Redo
+                                }
+                                _ => return,
+                            };
+                            event.prevent_default();
+                            revert_local_edit(direction);
+                        },
+                        pre {
+                            aria_hidden: "true",
+                            for (line_index, parts) in line_parts.into_iter().enumerate() {
+                                span {
+                                    class: if remote_edit.as_ref().is_some_and(|remote_edit| remote_edit.touches_line(line_index + 1)) { "remote-edit" },
+                                    for part in parts {
+                                        match part {
+                                            LinePart::Token(token) => rsx! {
+                                                span { class: token.kind.css_class(), "{token.text}" }
+                                            },
+                                            LinePart::Caret(node_id) => rsx! {
+                                                span {
+                                                    class: "peer-caret",
+                                                    style: "--peer-colour: {peer_colour(node_id)};",
+                                                    "data-name": "{peer_name(node_id)}",
+                                                }
+                                            },
+                                        }
+                                    }
+                                    if let Some(author) = file_blame.as_ref().and_then(|file_blame| file_blame.line_authors.get(line_index).copied().flatten()) {
+                                        span {
+                                            class: "blame-annotation",
+                                            style: "color: {peer_colour(author)};",
+                                            "{blame_label(author)}"
+                                        }
+                                    }
+                                    "\n"
+                                }
+                            }
+                        }
+                        textarea {
+                            id: "file_content",
+                            readonly: true,
+                            spellcheck: false,
+                            wrap: "off",
+                            value: "{active_file.content}"
+                        }
+                    }
+
+                    if is_markdown {
+                        MarkdownPreview { content: active_file.content.clone() }
+                    }
                 }
             }
         }
-    } else {
-        rsx! {}
     }
 }
@@ -1,33 +1,232 @@
-use crate::services::automerge_service::{AutomergeCommand, FILES};
+use crate::services::automerge_service::{AutomergeCommand, BinaryFile, BINARY_FILES, FILES};
+use crate::services::presence_service::{
+    connected_peer_cursors, peer_colour, peer_name, stop_following, PeerCursor,
+};
 use dioxus::prelude::*;
+use iroh::NodeId;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Height of a file list row, which has to match `.file-list li` in the stylesheet.
+const ROW_HEIGHT_PX: f64 = 24.0;
+/// Rows which fit into the scroll container.
+const VISIBLE_ROWS: usize = 20;
+/// Rows rendered above and below the visible ones, so fast scrolling doesn't show gaps.
+const OVERSCAN_ROWS: usize = 5;
+
+/// Formats a file size with binary prefixes, like file managers do.
+pub fn format_size(size: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{value:.1} {unit}")
+}
+
+#[derive(Clone, PartialEq)]
+enum FileListEntry {
+    Text(String),
+    Binary(BinaryFile),
+}
+
+fn entry_key(entry: &FileListEntry) -> &str {
+    match entry {
+        FileListEntry::Text(file_name) => file_name,
+        FileListEntry::Binary(binary_file) => &binary_file.file_name,
+    }
+}
+
+/// Marks a file with the peers which have it open.
+#[component]
+fn PeerMarkers(peers: Vec<NodeId>) -> Element {
+    rsx! {
+        for node_id in peers {
+            " "
+            span {
+                class: "peer-marker",
+                style: "background: {peer_colour(node_id)};",
+                title: "{peer_name(node_id)}",
+            }
+        }
+    }
+}
 
+#[component]
+fn FileListRow(entry: FileListEntry, peers: Vec<NodeId>) -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+
+    match entry {
+        FileListEntry::Text(file_name) => rsx! {
+            li {
+                a {
+                    // TODO: use real href and router to allow permalinks
+                    href: "#",
+                    onclick: move |_| {
+                        stop_following();
+                        automerge_service.send(AutomergeCommand::SelectFile {
+                            file_name: file_name.clone()
+                        });
+                    },
+                    "{file_name}"
+                }
+                PeerMarkers { peers }
+            }
+        },
+        FileListEntry::Binary(binary_file) => rsx! {
+            li {
+                a {
+                    href: "#",
+                    onclick: move |_| {
+                        stop_following();
+                        automerge_service.send(AutomergeCommand::SelectBinaryFile {
+                            file_name: binary_file.file_name.clone()
+                        });
+                    },
+                    "{binary_file.file_name}"
+                }
+                small { " (binary, {format_size(binary_file.size)})" }
+                PeerMarkers { peers }
+            }
+        },
+    }
+}
+
+/// Lists all files, rendering only the rows scrolled into view, so documents with thousands of
+/// files stay responsive.
 #[component]
 pub fn FileList() -> Element {
-    let files = FILES.read().to_owned();
+    let entries = use_memo(|| {
+        let text_entries = FILES
+            .read()
+            .iter()
+            .cloned()
+            .map(FileListEntry::Text)
+            .collect::<Vec<_>>();
+        let binary_entries = BINARY_FILES
+            .read()
+            .iter()
+            .cloned()
+            .map(FileListEntry::Binary)
+            .collect::<Vec<_>>();
+        [text_entries, binary_entries].concat()
+    });
     let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let mut upload_error = use_signal(|| "".to_string());
+    let mut commit_message = use_signal(String::new);
+    let mut scroll_container = use_signal(|| None::<Rc<MountedData>>);
+    let mut scroll_top = use_signal(|| 0.0);
+
+    let onscroll = move |_| async move {
+        let Some(scroll_container) = scroll_container() else {
+            return;
+        };
+        if let Ok(offset) = scroll_container.get_scroll_offset().await {
+            scroll_top.set(offset.y);
+        }
+    };
+
+    let mut peers_by_file_name = HashMap::<String, Vec<NodeId>>::new();
+    for (node_id, cursors) in connected_peer_cursors() {
+        for file_name in cursors.iter().filter_map(PeerCursor::file_name) {
+            let peers = peers_by_file_name.entry(file_name).or_default();
+            if !peers.contains(&node_id) {
+                peers.push(node_id);
+            }
+        }
+    }
+
+    let entry_count = entries.read().len();
+    let first_row = ((scroll_top() / ROW_HEIGHT_PX) as usize)
+        .saturating_sub(OVERSCAN_ROWS)
+        .min(entry_count);
+    let end_row = (first_row + VISIBLE_ROWS + 2 * OVERSCAN_ROWS).min(entry_count);
+    let top_spacer_height = first_row as f64 * ROW_HEIGHT_PX;
+    let bottom_spacer_height = (entry_count - end_row) as f64 * ROW_HEIGHT_PX;
+    let max_height = VISIBLE_ROWS as f64 * ROW_HEIGHT_PX;
+
+    let onchange = move |event: FormEvent| async move {
+        upload_error.set("".to_string());
+        let Some(file_engine) = event.files() else {
+            return;
+        };
+        let message = commit_message.read().trim().to_owned();
+        commit_message.set(String::new());
+
+        for file_name in file_engine.files() {
+            let Some(content) = file_engine.read_file(&file_name).await else {
+                upload_error.set(format!("Failed to read {file_name}"));
+                continue;
+            };
+
+            automerge_service.send(AutomergeCommand::UploadFile {
+                file_name,
+                content,
+                message: (!message.is_empty()).then(|| message.clone()),
+            });
+        }
+    };
 
     rsx! {
         h3 { "Files" }
 
-        ul {
-            if files.is_empty() {
+        if entry_count == 0 {
+            ul {
                 li { "No files!" }
-            } else {
-                for file_name in files {
-                    li {
-                        a {
-                            // TODO: use real href and router to allow permalinks
-                            href: "#",
-                            onclick: move |_| {
-                                automerge_service.send(AutomergeCommand::SelectFile {
-                                    file_name: file_name.clone()
-                                });
-                            },
-                            "{file_name}"
+            }
+        } else {
+            div {
+                class: "file-list",
+                style: "max-height: {max_height}px",
+                onmounted: move |event| scroll_container.set(Some(event.data())),
+                onscroll,
+
+                div { style: "height: {top_spacer_height}px" }
+                ul {
+                    for entry in entries.read()[first_row..end_row].iter().cloned() {
+                        FileListRow {
+                            key: "{entry_key(&entry)}",
+                            peers: peers_by_file_name.get(entry_key(&entry)).cloned().unwrap_or_default(),
+                            entry
                         }
                     }
                 }
+                div { style: "height: {bottom_spacer_height}px" }
+            }
+            if entry_count > VISIBLE_ROWS {
+                small { "{entry_count} files" }
+            }
+        }
+
+        fieldset {
+            input {
+                placeholder: "commit message (optional)",
+                value: "{commit_message}",
+                oninput: move |event: FormEvent| commit_message.set(event.value()),
+            }
+
+            label {
+                for: "upload_files",
+                "upload files:"
             }
+
+            input {
+                id: "upload_files",
+                type: "file",
+                multiple: true,
+                onchange
+            }
+
+            "{upload_error}"
         }
     }
 }
@@ -0,0 +1,74 @@
+use dioxus::prelude::*;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+
+pub const SOURCE_ELEMENT_ID: &str = "file_content_editor";
+const PREVIEW_ELEMENT_ID: &str = "markdown_preview";
+
+/// Renders Markdown written by peers to HTML, showing raw HTML as text so it can't run scripts.
+fn render_markdown(content: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+    let parser = Parser::new_ext(content, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if dest_url
+            .trim_start()
+            .to_lowercase()
+            .starts_with("javascript:") =>
+        {
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url: CowStr::Borrowed("#"),
+                title,
+                id,
+            })
+        }
+        event => event,
+    });
+
+    let mut rendered = String::with_capacity(content.len());
+    html::push_html(&mut rendered, parser);
+    rendered
+}
+
+/// Scrolls one element to the same relative position as another one.
+pub fn sync_scroll_position(from_element_id: &str, to_element_id: &str) {
+    let eval = document::eval(
+        r#"
+        const [fromElementId, toElementId] = await dioxus.recv();
+        const from = document.getElementById(fromElementId);
+        const to = document.getElementById(toElementId);
+        if (from && to) {
+            const scrollable = from.scrollHeight - from.clientHeight;
+            const ratio = scrollable > 0 ? from.scrollTop / scrollable : 0;
+            to.scrollTop = ratio * (to.scrollHeight - to.clientHeight);
+        }
+        "#,
+    );
+    let _ = eval.send((from_element_id, to_element_id));
+}
+
+/// Keeps the preview at the scroll position of the source view.
+pub fn sync_preview_scroll_position() {
+    sync_scroll_position(SOURCE_ELEMENT_ID, PREVIEW_ELEMENT_ID);
+}
+
+#[component]
+pub fn MarkdownPreview(content: String) -> Element {
+    let rendered = use_memo(use_reactive!(|content| render_markdown(&content)));
+
+    rsx! {
+        div {
+            id: PREVIEW_ELEMENT_ID,
+            class: "markdown-preview",
+            onscroll: move |_| sync_scroll_position(PREVIEW_ELEMENT_ID, SOURCE_ELEMENT_ID),
+            dangerous_inner_html: "{rendered}"
+        }
+    }
+}
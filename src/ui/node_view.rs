@@ -1,6 +1,32 @@
-use crate::services::node_service::{NODE_EVENTS, NODE_INFO};
+use crate::services::node_service::NODE_INFO;
+use crate::services::presence_service::{PresenceCommand, LOCAL_DISPLAY_NAME};
 use dioxus::prelude::*;
 
+#[component]
+fn DisplayNameForm() -> Element {
+    let presence_service = use_coroutine_handle::<PresenceCommand>();
+    let display_name = LOCAL_DISPLAY_NAME.read().clone().unwrap_or_default();
+
+    rsx! {
+        fieldset {
+            label {
+                for: "display_name",
+                "display name:"
+            }
+
+            input {
+                id: "display_name",
+                name: "display_name",
+                placeholder: "shown to your peers",
+                value: "{display_name}",
+                onchange: move |event: FormEvent| {
+                    presence_service.send(PresenceCommand::SetDisplayName { name: event.value() });
+                }
+            }
+        }
+    }
+}
+
 #[component]
 pub fn NodeInfoView() -> Element {
     rsx! {
@@ -21,17 +47,14 @@ pub fn NodeInfoView() -> Element {
 
                         dt { "Ethersync passphrase:" }
                         dd { "{node_info.my_passphrase}" }
+
+                        dt { "Ethersync viewer passphrase (read-only):" }
+                        dd { "{node_info.viewer_passphrase}" }
                     }
                 }
             }
 
-            hr { }
-
-            ul {
-                for event in NODE_EVENTS.iter() {
-                    li { "{event}" }
-                }
-            }
+            DisplayNameForm { }
         }
     }
 }
@@ -0,0 +1,77 @@
+use crate::services::presence_service::{
+    connected_peer_cursors, peer_colour, peer_name, PeerCursor,
+};
+use async_std::task::sleep;
+use chrono::{DateTime, Local};
+use dioxus::prelude::*;
+use std::time::Duration;
+
+/// How often idle states are re-evaluated, as no message arrives when a peer goes idle.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+fn format_last_activity(received_at: DateTime<Local>, now: DateTime<Local>) -> String {
+    let minutes = (now - received_at).num_minutes();
+    match minutes {
+        0 => "active just now".to_string(),
+        1 => "active 1 minute ago".to_string(),
+        minutes if minutes < 60 => format!("active {minutes} minutes ago"),
+        _ => format!("active at {}", received_at.format("%H:%M")),
+    }
+}
+
+/// Where each connected peer is working, according to their latest presence.
+#[component]
+pub fn PresenceList() -> Element {
+    let mut now = use_signal(Local::now);
+    use_future(move || async move {
+        loop {
+            sleep(IDLE_CHECK_INTERVAL).await;
+            now.set(Local::now());
+        }
+    });
+
+    let peer_cursors = connected_peer_cursors();
+    if peer_cursors.is_empty() {
+        return rsx! {};
+    }
+
+    rsx! {
+        h3 { "Peers" }
+
+        ul {
+            class: "presence-list",
+            for (node_id, cursors) in peer_cursors {
+                li {
+                    key: "{node_id}",
+                    class: if cursors.first().is_none_or(|cursor| cursor.is_idle(now())) { "idle" },
+                    span {
+                        class: "peer-marker",
+                        style: "background: {peer_colour(node_id)};",
+                    }
+                    " {peer_name(node_id)} "
+                    // The most recently updated cursor tells when the peer was last active.
+                    match cursors.first() {
+                        Some(latest_cursor) => rsx! {
+                            if cursors.iter().all(|cursor| cursor.file_name().is_none()) {
+                                "no file open"
+                            }
+                            for file_name in cursors.iter().filter_map(PeerCursor::file_name) {
+                                code { "{file_name}" }
+                                " "
+                            }
+                            " "
+                            small {
+                                "({format_last_activity(latest_cursor.received_at, now())}"
+                                if latest_cursor.is_idle(now()) { ", idle" }
+                                ")"
+                            }
+                        },
+                        None => rsx! {
+                            small { "(no presence shared yet)" }
+                        },
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::services::automerge_service::AutomergeCommand;
+use crate::services::presence_service::stop_following;
+use crate::services::search_index::{SearchQuery, SearchResult, MAX_SEARCH_RESULTS, SEARCH_INDEX};
+use crate::ui::file_content_view::{TextSelection, SELECTION_TO_REVEAL};
+use dioxus::prelude::*;
+
+#[component]
+fn SearchResultView(result: SearchResult, replaced_line: Option<String>) -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+
+    let (match_start, match_end) = result.line_match;
+    let before = result.line[..match_start].trim_start().to_owned();
+    let matched = result.line[match_start..match_end].to_owned();
+    let after = result.line[match_end..].trim_end().to_owned();
+    let location = format!("{}:{}", result.file_name, result.line_number);
+
+    rsx! {
+        li {
+            a {
+                href: "#",
+                onclick: move |_| {
+                    stop_following();
+                    *SELECTION_TO_REVEAL.write() = Some(TextSelection {
+                        file_name: result.file_name.clone(),
+                        line_number: result.line_number,
+                        selection_start: result.selection_start,
+                        selection_end: result.selection_end,
+                    });
+                    automerge_service.send(AutomergeCommand::SelectFile {
+                        file_name: result.file_name.clone(),
+                    });
+                },
+                code { "{location}" }
+            }
+            " "
+            code {
+                "{before}"
+                mark { "{matched}" }
+                "{after}"
+            }
+            if let Some(replaced_line) = replaced_line {
+                br {}
+                "→ "
+                code { class: "replaced-line", "{replaced_line.trim()}" }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn SearchView() -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let mut query = use_signal(SearchQuery::default);
+    // The replacement is only previewed and applied while this is set.
+    let mut replacement = use_signal(|| None::<String>);
+    let mut commit_message = use_signal(String::new);
+
+    // File contents are only read into the index once somebody searches.
+    use_effect(move || {
+        if !query.read().pattern.is_empty() && !SEARCH_INDEX.read().is_complete() {
+            automerge_service.send(AutomergeCommand::BuildSearchIndex);
+        }
+    });
+
+    let results = use_memo(move || {
+        let search_index = SEARCH_INDEX.read();
+        let results = match replacement.read().as_deref() {
+            Some(replacement) => search_index.preview_replacements(&query.read(), replacement),
+            None => search_index.search(&query.read()).map(|results| {
+                results
+                    .into_iter()
+                    .map(|result| (result, String::new()))
+                    .collect()
+            }),
+        };
+        results.map_err(|error| error.to_string())
+    });
+    let replace_all = move |_| {
+        if let Some(replacement) = replacement.read().clone() {
+            let message = commit_message.read().trim().to_owned();
+            automerge_service.send(AutomergeCommand::ReplaceAll {
+                query: query.read().clone(),
+                replacement,
+                message: (!message.is_empty()).then_some(message),
+            });
+            commit_message.set(String::new());
+        }
+    };
+
+    rsx! {
+        section {
+            h2 { "Search" }
+
+            fieldset {
+                input {
+                    placeholder: "search in all files",
+                    value: "{query.read().pattern}",
+                    oninput: move |event: FormEvent| query.write().pattern = event.value(),
+                }
+
+                label {
+                    input {
+                        r#type: "checkbox",
+                        checked: query.read().case_sensitive,
+                        oninput: move |event: FormEvent| {
+                            query.write().case_sensitive = event.checked();
+                        },
+                    }
+                    "case sensitive"
+                }
+
+                label {
+                    input {
+                        r#type: "checkbox",
+                        checked: query.read().regex,
+                        oninput: move |event: FormEvent| query.write().regex = event.checked(),
+                    }
+                    "regex"
+                }
+
+                label {
+                    input {
+                        r#type: "checkbox",
+                        checked: replacement.read().is_some(),
+                        oninput: move |event: FormEvent| {
+                            replacement.set(event.checked().then(String::new));
+                        },
+                    }
+                    "replace"
+                }
+            }
+
+            if let Some(replacement_text) = replacement.read().clone() {
+                fieldset {
+                    input {
+                        placeholder: if query.read().regex { "replace with, $1 for groups" } else { "replace with" },
+                        value: "{replacement_text}",
+                        oninput: move |event: FormEvent| replacement.set(Some(event.value())),
+                    }
+
+                    input {
+                        placeholder: "commit message (optional)",
+                        value: "{commit_message}",
+                        oninput: move |event: FormEvent| commit_message.set(event.value()),
+                    }
+
+                    button {
+                        disabled: !results.read().as_ref().is_ok_and(|results| !results.is_empty()),
+                        onclick: replace_all,
+                        "replace all"
+                    }
+                }
+            }
+
+            match &*results.read() {
+                Ok(_) if !SEARCH_INDEX.read().is_complete() => rsx! {
+                    if !query.read().pattern.is_empty() {
+                        p { "Indexing files…" }
+                    }
+                },
+                Ok(results) if results.is_empty() => rsx! {
+                    if !query.read().pattern.is_empty() {
+                        p { "No matches!" }
+                    }
+                },
+                Ok(results) => rsx! {
+                    if results.len() >= MAX_SEARCH_RESULTS {
+                        p {
+                            "Showing the first {MAX_SEARCH_RESULTS} matches only."
+                            if replacement.read().is_some() {
+                                " Replacing changes all of them."
+                            }
+                        }
+                    }
+                    ul {
+                        class: "search-results",
+                        for (result, replaced_line) in results.iter().cloned() {
+                            SearchResultView {
+                                result,
+                                replaced_line: replacement.read().is_some().then_some(replaced_line),
+                            }
+                        }
+                    }
+                },
+                Err(error) => rsx! {
+                    pre { class: "log-error", "invalid pattern: {error}" }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::services::automerge_service::AutomergeCommand;
+use crate::services::session_recorder::{SessionRecording, REPLAYED_RECORDING, SESSION_RECORDING};
+use crate::ui::download::download_file;
+use chrono::Local;
+use dioxus::prelude::*;
+
+fn stop_recording() -> anyhow::Result<()> {
+    let Some(recording) = SESSION_RECORDING.write().take() else {
+        return Ok(());
+    };
+
+    let file_name = format!(
+        "ethersync-web-{}.recording",
+        Local::now().format("%Y-%m-%d-%H%M%S")
+    );
+    download_file(&file_name, "application/octet-stream", recording.encode()?);
+    Ok(())
+}
+
+#[component]
+pub fn SessionRecorderView() -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let mut recorder_error = use_signal(|| "".to_string());
+
+    let onchange = move |event: FormEvent| async move {
+        recorder_error.set("".to_string());
+        let Some(file_engine) = event.files() else {
+            return;
+        };
+
+        for file_name in file_engine.files() {
+            let Some(bytes) = file_engine.read_file(&file_name).await else {
+                recorder_error.set(format!("Failed to read {file_name}"));
+                continue;
+            };
+
+            match SessionRecording::decode(&bytes) {
+                Ok(recording) => automerge_service.send(AutomergeCommand::ReplayRecording {
+                    recording: Box::new(recording),
+                }),
+                Err(error) => recorder_error.set(format!("{error}")),
+            }
+        }
+    };
+
+    rsx! {
+        section {
+            h2 { "Session Recorder" }
+
+            "{recorder_error}"
+
+            fieldset {
+                match SESSION_RECORDING.read().as_ref() {
+                    None => rsx! {
+                        button {
+                            onclick: move |_| automerge_service.send(AutomergeCommand::StartRecording),
+                            "start recording"
+                        }
+                    },
+                    Some(recording) => rsx! {
+                        span { "recording… ({recording.frames.len()} frames)" }
+
+                        button {
+                            onclick: move |_| {
+                                if let Err(error) = stop_recording() {
+                                    recorder_error.set(format!("{error}"));
+                                }
+                            },
+                            "stop and download"
+                        }
+                    }
+                }
+            }
+
+            fieldset {
+                label {
+                    for: "replay_recording",
+                    "replay recording into a separate document:"
+                }
+
+                input {
+                    id: "replay_recording",
+                    type: "file",
+                    accept: ".recording",
+                    onchange
+                }
+            }
+
+            if let Some(replayed_recording) = REPLAYED_RECORDING.read().as_ref() {
+                h3 { "Replayed document ({replayed_recording.frame_count} frames)" }
+
+                for (file_name, content) in &replayed_recording.file_contents {
+                    details {
+                        key: "{file_name}",
+                        summary { code { "{file_name}" } }
+                        pre { "{content}" }
+                    }
+                }
+
+                button {
+                    onclick: move |_| *REPLAYED_RECORDING.write() = None,
+                    "dismiss"
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::services::automerge_service::AutomergeCommand;
+use crate::services::snapshots::{FileComparison, Snapshot, SNAPSHOTS, SNAPSHOT_COMPARISON};
+use chrono::{DateTime, Local};
+use dioxus::prelude::*;
+
+fn format_created_at(created_at_millis: i64) -> String {
+    DateTime::from_timestamp_millis(created_at_millis)
+        .map(|created_at| {
+            created_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+#[component]
+fn SnapshotListItem(snapshot: Snapshot) -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let created_at = format_created_at(snapshot.created_at_millis);
+    let name = snapshot.name;
+
+    rsx! {
+        li {
+            "{name} "
+            small { "({created_at})" }
+            " "
+            button {
+                onclick: {
+                    let name = name.clone();
+                    move |_| automerge_service.send(AutomergeCommand::CompareWithSnapshot {
+                        name: name.clone(),
+                    })
+                },
+                "compare"
+            }
+            button {
+                onclick: {
+                    let name = name.clone();
+                    move |_| automerge_service.send(AutomergeCommand::RestoreSnapshot {
+                        name: name.clone(),
+                        file_name: None,
+                    })
+                },
+                "restore all"
+            }
+            button {
+                onclick: move |_| automerge_service.send(AutomergeCommand::DeleteSnapshot {
+                    name: name.clone(),
+                }),
+                "delete"
+            }
+        }
+    }
+}
+
+#[component]
+fn FileComparisonView(snapshot_name: String, file: FileComparison) -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let file_name = file.file_name.clone();
+
+    rsx! {
+        details {
+            summary {
+                code { "{file.file_name}" }
+                " {file.change} "
+                button {
+                    onclick: move |_| automerge_service.send(AutomergeCommand::RestoreSnapshot {
+                        name: snapshot_name.clone(),
+                        file_name: Some(file_name.clone()),
+                    }),
+                    "restore"
+                }
+            }
+            pre { class: "unified-diff", "{file.unified_diff}" }
+        }
+    }
+}
+
+#[component]
+pub fn SnapshotView() -> Element {
+    let automerge_service = use_coroutine_handle::<AutomergeCommand>();
+    let mut snapshot_name = use_signal(String::new);
+
+    let create_snapshot = move |_| {
+        automerge_service.send(AutomergeCommand::CreateSnapshot {
+            name: snapshot_name.read().clone(),
+        });
+        snapshot_name.set(String::new());
+    };
+
+    rsx! {
+        section {
+            h2 { "Snapshots" }
+
+            fieldset {
+                input {
+                    placeholder: "snapshot name",
+                    value: "{snapshot_name}",
+                    oninput: move |event: FormEvent| snapshot_name.set(event.value()),
+                }
+
+                button {
+                    disabled: snapshot_name.read().trim().is_empty(),
+                    onclick: create_snapshot,
+                    "create snapshot"
+                }
+            }
+
+            ul {
+                for snapshot in SNAPSHOTS.read().iter().cloned() {
+                    SnapshotListItem { key: "{snapshot.name}", snapshot }
+                }
+            }
+
+            if let Some(comparison) = SNAPSHOT_COMPARISON.read().clone() {
+                h3 {
+                    "Changes since '{comparison.snapshot_name}' "
+                    button {
+                        onclick: move |_| *SNAPSHOT_COMPARISON.write() = None,
+                        "close"
+                    }
+                }
+
+                if comparison.files.is_empty() {
+                    p { "No changes!" }
+                }
+
+                for file in comparison.files {
+                    FileComparisonView {
+                        key: "{file.file_name}",
+                        snapshot_name: comparison.snapshot_name.clone(),
+                        file,
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::services::automerge_service::{SyncStatus, SYNC_STATUS};
+use crate::services::connection_service::CONNECTED_PEERS;
+use dioxus::prelude::*;
+
+fn sync_status_class(sync_status: &SyncStatus) -> &'static str {
+    match sync_status {
+        SyncStatus::InSync => "sync-status in-sync",
+        SyncStatus::Syncing { .. } => "sync-status syncing",
+        SyncStatus::Diverged { .. } => "sync-status diverged",
+    }
+}
+
+#[component]
+pub fn SyncStatusBadge(sync_status: SyncStatus) -> Element {
+    rsx! {
+        span {
+            class: sync_status_class(&sync_status),
+            "{sync_status}"
+        }
+    }
+}
+
+/// Combined status of all connected peers, so users know when it is safe to close the tab.
+#[component]
+pub fn OverallSyncStatusBadge() -> Element {
+    let sync_status = SYNC_STATUS.read();
+    let mut statuses = CONNECTED_PEERS
+        .read()
+        .iter()
+        .map(|peer| {
+            sync_status
+                .get(&peer.node_id)
+                .copied()
+                .unwrap_or(SyncStatus::Syncing {
+                    changes_to_send: 0,
+                    missing_heads: 0,
+                })
+        })
+        .collect::<Vec<_>>();
+
+    // Show the least synced peer.
+    statuses.sort_by_key(|status| match status {
+        SyncStatus::InSync => 0,
+        SyncStatus::Syncing { .. } => 1,
+        SyncStatus::Diverged { .. } => 2,
+    });
+
+    match statuses.last() {
+        None => rsx! {
+            span {
+                class: "sync-status",
+                "offline"
+            }
+        },
+        Some(sync_status) => rsx! {
+            SyncStatusBadge { sync_status: *sync_status }
+        },
+    }
+}